@@ -0,0 +1,325 @@
+//! Archives which bundle processed shaders with their metadata, for loading at runtime.
+//!
+//! `glossy_codegen::Config::archive` writes every shader built for every language to a single
+//! archive file, which can be read with `Archive::read()` instead of embedding the shaders in the
+//! binary.
+//!
+//! # Format
+//!
+//! All integers are little-endian. A string is a `u32` byte length followed by UTF-8 bytes, and
+//! a list is a `u32` count followed by its elements.
+//!
+//! ```text
+//! magic       8 bytes     "GLOSSYAR"
+//! version     u32         1
+//! files       list of:
+//!     id          u32         the file id used by `#line` directives
+//!     name        string
+//! shaders     list of:
+//!     name        string      the file name of the shader
+//!     language    string      the `Language::short_name()` it was built for
+//!     stage       u8          0 = vertex, 1 = fragment, 2 = other
+//!     version     string      the arguments of its `#version` directive, e.g. "300 es"
+//!     source      string      the processed source
+//!     includes    list of string
+//!     has_info    u8          1 if reflection info follows, 0 otherwise
+//!     inputs      list of variable
+//!     uniforms    list of variable
+//!     textures    list of variable
+//!     uniform_total_size  u32
+//! ```
+//!
+//! The reflection info (`inputs` to `uniform_total_size`) is only present if `has_info` is 1. A
+//! variable is:
+//!
+//! ```text
+//! name        string
+//! type        u8          0 = float, 1 = int, 2 = bool, 3 = 2D texture, 4 = 3D texture,
+//!                         5 = cube texture, 6 = 2D shadow texture, 7 = 2D array texture,
+//!                         255 = other
+//! precision   u8          0 = high, 1 = medium, 2 = low
+//! vec_size    u32
+//! mat_size    u32
+//! array_size  u32         0xffffffff if the variable is not an array
+//! location    u32         0xffffffff if the variable has no location
+//! ```
+
+use std::io::{self, Read, Write};
+use Language;
+use reflect::{BasicType, Precision};
+
+const MAGIC: &'static [u8; 8] = b"GLOSSYAR";
+const FORMAT_VERSION: u32 = 1;
+const NONE: u32 = 0xffff_ffff;
+
+/// The stage of a shader in an archive.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+    Other,
+}
+
+/// A single input, uniform or texture used by an optimized shader. This is the owned equivalent
+/// of `reflect::Variable`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub ty: BasicType,
+    pub precision: Precision,
+    pub vec_size: u32,
+    pub mat_size: u32,
+    pub array_size: Option<u32>,
+    pub location: Option<u32>,
+}
+
+/// The inputs, uniforms and textures which remain in a shader after optimization. This is the
+/// owned equivalent of `reflect::ShaderInfo`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ShaderInfo {
+    pub inputs: Vec<Variable>,
+    pub uniforms: Vec<Variable>,
+    pub textures: Vec<Variable>,
+    pub uniform_total_size: u32,
+}
+
+/// A processed shader in an archive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Shader {
+    /// The file name of the shader.
+    pub name: String,
+    /// The language the shader was built for.
+    pub lang: Language,
+    pub stage: Stage,
+    /// The arguments of the `#version` directive of the shader, e.g. `300 es`.
+    pub version: String,
+    /// The processed source.
+    pub source: String,
+    /// The names of the files which the shader includes, directly or indirectly.
+    pub includes: Vec<String>,
+    /// The reflection info, for shaders optimized by glsl-optimizer.
+    pub info: Option<ShaderInfo>,
+}
+
+/// A bundle of processed shaders and the table of the files they were built from.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Archive {
+    /// The id and name of every file, as in `files::FileMap`.
+    pub files: Vec<(u32, String)>,
+    pub shaders: Vec<Shader>,
+}
+
+impl Archive {
+    /// Returns the shader with the given name built for a language.
+    pub fn shader(&self, name: &str, lang: Language) -> Option<&Shader> {
+        self.shaders.iter().find(|shader| shader.name == name && shader.lang == lang)
+    }
+
+    /// Returns the name of the file with the given id.
+    pub fn file_name(&self, id: u32) -> Option<&str> {
+        self.files.iter().find(|&&(file_id, _)| file_id == id).map(|&(_, ref name)| name.as_str())
+    }
+
+    /// Reads an archive. Errors in the format are reported as `io::ErrorKind::InvalidData`.
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Archive> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a glossy shader archive"));
+        }
+        let version = read_u32(r)?;
+        if version != FORMAT_VERSION {
+            return Err(invalid(&format!("unsupported archive version {}", version)));
+        }
+
+        let files = read_list(r, |r| Ok((read_u32(r)?, read_string(r)?)))?;
+        let shaders = read_list(r, |r| {
+            let name = read_string(r)?;
+            let lang = read_string(r)?;
+            let lang = Language::from_short_name(&lang)
+                .ok_or_else(|| invalid(&format!("unknown language \"{}\"", lang)))?;
+            let stage = match read_u8(r)? {
+                0 => Stage::Vertex,
+                1 => Stage::Fragment,
+                2 => Stage::Other,
+                stage => return Err(invalid(&format!("unknown stage {}", stage))),
+            };
+            Ok(Shader {
+                name: name,
+                lang: lang,
+                stage: stage,
+                version: read_string(r)?,
+                source: read_string(r)?,
+                includes: read_list(r, read_string)?,
+                info: if read_u8(r)? != 0 { Some(read_info(r)?) } else { None },
+            })
+        })?;
+        Ok(Archive {
+            files: files,
+            shaders: shaders,
+        })
+    }
+
+    /// Writes the archive.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        write_u32(w, FORMAT_VERSION)?;
+
+        write_u32(w, self.files.len() as u32)?;
+        for &(id, ref name) in self.files.iter() {
+            write_u32(w, id)?;
+            write_string(w, name)?;
+        }
+
+        write_u32(w, self.shaders.len() as u32)?;
+        for shader in self.shaders.iter() {
+            write_string(w, &shader.name)?;
+            write_string(w, shader.lang.short_name())?;
+            let stage = match shader.stage {
+                Stage::Vertex => 0,
+                Stage::Fragment => 1,
+                Stage::Other => 2,
+            };
+            w.write_all(&[stage])?;
+            write_string(w, &shader.version)?;
+            write_string(w, &shader.source)?;
+            write_u32(w, shader.includes.len() as u32)?;
+            for include in shader.includes.iter() {
+                write_string(w, include)?;
+            }
+            match shader.info {
+                Some(ref info) => {
+                    w.write_all(&[1])?;
+                    write_info(w, info)?;
+                }
+                None => w.write_all(&[0])?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24)
+}
+
+fn read_optional_u32<R: Read>(r: &mut R) -> io::Result<Option<u32>> {
+    read_u32(r).map(|value| if value == NONE { None } else { Some(value) })
+}
+
+fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = read_u32(r)? as u64;
+    let mut buf = Vec::new();
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated string"));
+    }
+    String::from_utf8(buf).map_err(|_| invalid("string is not valid UTF-8"))
+}
+
+fn read_list<R: Read, T, F>(r: &mut R, mut f: F) -> io::Result<Vec<T>>
+    where F: FnMut(&mut R) -> io::Result<T>
+{
+    let count = read_u32(r)?;
+    let mut list = Vec::new();
+    for _ in 0..count {
+        list.push(f(r)?);
+    }
+    Ok(list)
+}
+
+fn read_info<R: Read>(r: &mut R) -> io::Result<ShaderInfo> {
+    Ok(ShaderInfo {
+        inputs: read_list(r, read_variable)?,
+        uniforms: read_list(r, read_variable)?,
+        textures: read_list(r, read_variable)?,
+        uniform_total_size: read_u32(r)?,
+    })
+}
+
+fn read_variable<R: Read>(r: &mut R) -> io::Result<Variable> {
+    let name = read_string(r)?;
+    let ty = match read_u8(r)? {
+        0 => BasicType::Float,
+        1 => BasicType::Int,
+        2 => BasicType::Bool,
+        3 => BasicType::Texture2D,
+        4 => BasicType::Texture3D,
+        5 => BasicType::TextureCube,
+        6 => BasicType::Texture2DShadow,
+        7 => BasicType::Texture2DArray,
+        255 => BasicType::Other,
+        ty => return Err(invalid(&format!("unknown type {}", ty))),
+    };
+    let precision = match read_u8(r)? {
+        0 => Precision::High,
+        1 => Precision::Medium,
+        2 => Precision::Low,
+        precision => return Err(invalid(&format!("unknown precision {}", precision))),
+    };
+    Ok(Variable {
+        name: name,
+        ty: ty,
+        precision: precision,
+        vec_size: read_u32(r)?,
+        mat_size: read_u32(r)?,
+        array_size: read_optional_u32(r)?,
+        location: read_optional_u32(r)?,
+    })
+}
+
+fn write_u32<W: Write>(w: &mut W, value: u32) -> io::Result<()> {
+    w.write_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_u32(w, s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_info<W: Write>(w: &mut W, info: &ShaderInfo) -> io::Result<()> {
+    for vars in [&info.inputs, &info.uniforms, &info.textures].iter() {
+        write_u32(w, vars.len() as u32)?;
+        for var in vars.iter() {
+            write_variable(w, var)?;
+        }
+    }
+    write_u32(w, info.uniform_total_size)
+}
+
+fn write_variable<W: Write>(w: &mut W, var: &Variable) -> io::Result<()> {
+    write_string(w, &var.name)?;
+    let ty = match var.ty {
+        BasicType::Float => 0,
+        BasicType::Int => 1,
+        BasicType::Bool => 2,
+        BasicType::Texture2D => 3,
+        BasicType::Texture3D => 4,
+        BasicType::TextureCube => 5,
+        BasicType::Texture2DShadow => 6,
+        BasicType::Texture2DArray => 7,
+        BasicType::Other => 255,
+    };
+    let precision = match var.precision {
+        Precision::High => 0,
+        Precision::Medium => 1,
+        Precision::Low => 2,
+    };
+    w.write_all(&[ty, precision])?;
+    write_u32(w, var.vec_size)?;
+    write_u32(w, var.mat_size)?;
+    write_u32(w, var.array_size.unwrap_or(NONE))?;
+    write_u32(w, var.location.unwrap_or(NONE))
+}
@@ -0,0 +1,45 @@
+//! Compression of the shader sources embedded in a binary.
+//!
+//! When `glossy_codegen::Config::compress` is used, every shader source is also written
+//! compressed with raw DEFLATE, which can be embedded with the `shader_compressed!` macro. Each
+//! source is decompressed the first time it is accessed, and cached for the rest of the program.
+//! This is only available when the 'compress' feature is enabled.
+
+extern crate miniz_oxide;
+
+use std::sync::OnceLock;
+
+/// Compresses a source with raw DEFLATE at the highest compression level.
+pub fn compress(source: &str) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec(source.as_bytes(), 10)
+}
+
+/// Decompresses a source compressed with `compress()`.
+///
+/// This panics if the data is not valid DEFLATE, or does not decompress to UTF-8.
+pub fn decompress(data: &[u8]) -> String {
+    let bytes = miniz_oxide::inflate::decompress_to_vec(data)
+        .unwrap_or_else(|err| panic!("invalid compressed shader source: {:?}", err));
+    String::from_utf8(bytes).expect("compressed shader source is not valid UTF-8")
+}
+
+/// A compressed source which is decompressed on first access, as used by `shader_compressed!`.
+pub struct Cache {
+    data: &'static [u8],
+    source: OnceLock<String>,
+}
+
+impl Cache {
+    /// Create a cache for a compressed source.
+    pub const fn new(data: &'static [u8]) -> Cache {
+        Cache {
+            data: data,
+            source: OnceLock::new(),
+        }
+    }
+
+    /// Returns the decompressed source, decompressing it if this is the first access.
+    pub fn get(&self) -> &str {
+        self.source.get_or_init(|| decompress(self.data))
+    }
+}
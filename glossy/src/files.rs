@@ -0,0 +1,49 @@
+//! The table of files which make up the shaders built by `glossy_codegen`.
+//!
+//! The build script generates the table, which should be declared once in the crate that uses
+//! the shaders:
+//!
+//! ```ignore
+//! static FILES: glossy::files::FileMap = shader_files!();
+//!
+//! println!("{:?}", FILES.name(1));
+//! ```
+
+use log;
+
+/// A table of the ids and names of the files which make up the built shaders, and of the files
+/// each shader includes. The ids are those used by the `#line` directives of the shaders.
+#[derive(Copy, Clone, Debug)]
+pub struct FileMap {
+    /// The id and name of every file, ordered by id.
+    pub files: &'static [(u32, &'static str)],
+    /// The name of every shader, with the names of the files it includes directly or
+    /// indirectly, in the order they are first included.
+    pub shaders: &'static [(&'static str, &'static [&'static str])],
+}
+
+impl FileMap {
+    /// Returns the name of the file with the given id.
+    pub fn name(&self, id: u32) -> Option<&'static str> {
+        self.files
+            .binary_search_by_key(&id, |&(id, _)| id)
+            .ok()
+            .map(|idx| self.files[idx].1)
+    }
+
+    /// Returns the id of the file with the given name.
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.files.iter().find(|&&(_, n)| n == name).map(|&(id, _)| id)
+    }
+
+    /// Returns the names of the files which a shader includes, directly or indirectly.
+    pub fn includes(&self, shader: &str) -> Option<&'static [&'static str]> {
+        self.shaders.iter().find(|&&(name, _)| name == shader).map(|&(_, includes)| includes)
+    }
+
+    /// Rewrites the file ids in a GPU driver info log with the names of the files. See
+    /// `log::rewrite_file_ids()`.
+    pub fn translate_log(&self, log: &str) -> String {
+        log::rewrite_file_ids(log, |id| self.name(id))
+    }
+}
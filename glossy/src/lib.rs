@@ -48,16 +48,221 @@
 //! }
 //! ```
 
+pub mod archive;
+#[cfg(feature = "compress")]
+pub mod compress;
+pub mod files;
+pub mod log;
+pub mod obfuscate;
+pub mod reflect;
+pub mod source_map;
+
+/// A GLSL dialect targeted by glossy.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Language {
+    /// Desktop OpenGL with any GLSL version, defaulting to GLSL 1.10.
+    OpenGl,
+    /// An OpenGL 3.3 core profile context, supporting GLSL 1.40 through 3.30.
+    OpenGl33Core,
+    /// An OpenGL 4.x core profile context, supporting GLSL 1.40 through 4.60.
+    OpenGl4x,
+    /// OpenGL ES 2.0, supporting GLSL ES 1.00.
+    OpenGlEs20,
+    /// OpenGL ES 3.0, supporting GLSL ES 1.00 and 3.00.
+    OpenGlEs30,
+    /// OpenGL ES 3.1, supporting GLSL ES 1.00 through 3.10.
+    OpenGlEs31,
+    /// OpenGL ES 3.2, supporting GLSL ES 1.00 through 3.20.
+    OpenGlEs32,
+    /// WebGL 1, supporting GLSL ES 1.00 with the additional restrictions imposed by WebGL.
+    WebGl1,
+    /// WebGL 2, supporting GLSL ES 1.00 and 3.00 with the additional restrictions imposed by
+    /// WebGL.
+    WebGl2,
+}
+
+impl Language {
+    /// Returns every language glossy can target.
+    pub fn all() -> &'static [Language] {
+        &[Language::OpenGl,
+          Language::OpenGl33Core,
+          Language::OpenGl4x,
+          Language::OpenGlEs20,
+          Language::OpenGlEs30,
+          Language::OpenGlEs31,
+          Language::OpenGlEs32,
+          Language::WebGl1,
+          Language::WebGl2]
+    }
+
+    /// Returns a short identifier for the language, which is used as the name of its output
+    /// directory in multi-target builds and as the value of the `glossy_lang` cfg.
+    pub fn short_name(&self) -> &'static str {
+        match *self {
+            Language::OpenGl => "gl",
+            Language::OpenGl33Core => "gl33",
+            Language::OpenGl4x => "gl4",
+            Language::OpenGlEs20 => "gles20",
+            Language::OpenGlEs30 => "gles30",
+            Language::OpenGlEs31 => "gles31",
+            Language::OpenGlEs32 => "gles32",
+            Language::WebGl1 => "webgl1",
+            Language::WebGl2 => "webgl2",
+        }
+    }
+
+    /// Returns the language with the given `short_name()`.
+    pub fn from_short_name(name: &str) -> Option<Language> {
+        match name {
+            "gl" => Some(Language::OpenGl),
+            "gl33" => Some(Language::OpenGl33Core),
+            "gl4" => Some(Language::OpenGl4x),
+            "gles20" => Some(Language::OpenGlEs20),
+            "gles30" => Some(Language::OpenGlEs30),
+            "gles31" => Some(Language::OpenGlEs31),
+            "gles32" => Some(Language::OpenGlEs32),
+            "webgl1" => Some(Language::WebGl1),
+            "webgl2" => Some(Language::WebGl2),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the language uses GLSL ES, which is the case for every version of OpenGL
+    /// ES and WebGL.
+    pub fn is_gles(&self) -> bool {
+        match *self {
+            Language::OpenGl |
+            Language::OpenGl33Core |
+            Language::OpenGl4x => false,
+            Language::OpenGlEs20 |
+            Language::OpenGlEs30 |
+            Language::OpenGlEs31 |
+            Language::OpenGlEs32 |
+            Language::WebGl1 |
+            Language::WebGl2 => true,
+        }
+    }
+
+    /// Returns true if the language is a version of WebGL.
+    pub fn is_webgl(&self) -> bool {
+        *self == Language::WebGl1 || *self == Language::WebGl2
+    }
+}
+
 /// Evaluates to a string of the glossy-processed shader source of the given filename.
+///
+/// With a single argument, this is the source built for the language passed to
+/// `glossy_codegen::Config::new`. The build script also sets the `glossy_lang` cfg to the
+/// `short_name()` of that language, so code can be conditionally compiled on it with e.g.
+/// `#[cfg(glossy_lang = "gles30")]`.
+///
+/// With a second `glossy::Language` argument, the source built for that language is selected at
+/// runtime. This requires the language to be the one passed to `Config::new` or one of the build
+/// script's `Config::targets`, and panics otherwise.
 #[macro_export]
 macro_rules! shader {
-    ($file:expr) => (include_str!(concat!(env!("OUT_DIR"), "/", $file)))
+    ($file:expr) => (include_str!(concat!(env!("OUT_DIR"), "/", $file)));
+    ($file:expr, $lang:expr) => {
+        include!(concat!(env!("OUT_DIR"), "/", $file, ".targets.rs"))($lang)
+            .expect("shader was not built for the requested language")
+    };
+}
+
+/// Evaluates to the `glossy::files::FileMap` generated by the build script, which lists the
+/// ids and names of the files that make up the shaders. This should be used once, to declare a
+/// `static`:
+///
+/// ```ignore
+/// static FILES: glossy::files::FileMap = shader_files!();
+/// ```
+#[macro_export]
+macro_rules! shader_files {
+    () => (include!(concat!(env!("OUT_DIR"), "/glossy_files.rs")))
 }
 
 /// Returns the name of the shader file for the given __FILE__ value.
+///
+/// This includes the whole file table at every use; prefer declaring it once with
+/// `shader_files!` and calling `FileMap::name()`.
 #[macro_export]
 macro_rules! shader_id_to_name {
     ($id:expr) => {
-        include!(concat!(env!("OUT_DIR"), "/glossy_file_id_to_name.rs"))($id)
+        include!(concat!(env!("OUT_DIR"), "/glossy_files.rs")).name($id)
     }
 }
+
+/// Rewrites the file ids in a GPU driver info log with the names of the files, as listed in the
+/// file table generated by the build script. This supports the log formats of the common
+/// drivers, e.g. `0(12)` (NVIDIA), `0:12(3)` (Mesa) and `ERROR: 0:12:` (AMD).
+///
+/// This includes the whole file table at every use; prefer declaring it once with
+/// `shader_files!` and calling `FileMap::translate_log()`.
+#[macro_export]
+macro_rules! translate_shader_log {
+    ($log:expr) => {
+        include!(concat!(env!("OUT_DIR"), "/glossy_files.rs")).translate_log($log)
+    }
+}
+
+/// Evaluates to a `&'static glossy::reflect::ShaderInfo` describing the inputs, uniforms and
+/// textures used by the given shader after optimization.
+///
+/// This is only available for shaders which were optimized by glsl-optimizer.
+#[macro_export]
+macro_rules! shader_info {
+    ($file:expr) => (include!(concat!(env!("OUT_DIR"), "/", $file, ".info.rs")))
+}
+
+/// Evaluates to a `&'static glossy::source_map::SourceMap` which maps the lines of the given
+/// shader back to the files and lines they came from, e.g. to translate driver info logs.
+#[macro_export]
+macro_rules! shader_source_map {
+    ($file:expr) => (include!(concat!(env!("OUT_DIR"), "/", $file, ".map.rs")))
+}
+
+/// Evaluates to a `&'static [u32]` of the SPIR-V compiled from the given shader.
+///
+/// This is only available for shaders built with `glossy_codegen::Config::spirv`, which requires
+/// the 'spirv' feature of glossy_codegen.
+#[macro_export]
+macro_rules! shader_spirv {
+    ($file:expr) => (include!(concat!(env!("OUT_DIR"), "/", $file, ".spv.rs")))
+}
+
+/// Evaluates to a `&'static str` of the WGSL translated from the given shader.
+///
+/// This is only available for shaders built with `glossy_codegen::Config::wgsl`, which requires
+/// the 'wgsl' feature of glossy_codegen.
+#[macro_export]
+macro_rules! shader_wgsl {
+    ($file:expr) => (include_str!(concat!(env!("OUT_DIR"), "/", $file, ".wgsl")))
+}
+
+/// Evaluates to a `String` of the glossy-processed shader source of the given filename, decoded
+/// from the obfuscated copy embedded in the binary.
+///
+/// This is only available for shaders built with `glossy_codegen::Config::obfuscate`. The
+/// source is decoded every time this is evaluated.
+#[macro_export]
+macro_rules! shader_obfuscated {
+    ($file:expr) => (
+        $crate::obfuscate::decode(
+            include_bytes!(concat!(env!("OUT_DIR"), "/", $file, ".obf")),
+            include!(concat!(env!("OUT_DIR"), "/glossy_obfuscation_key.rs")))
+    )
+}
+
+/// Evaluates to a `&'static str` of the glossy-processed shader source of the given filename,
+/// decompressed from the compressed copy embedded in the binary on first access.
+///
+/// This is only available for shaders built with `glossy_codegen::Config::compress`, and when the
+/// 'compress' feature of glossy is enabled.
+#[cfg(feature = "compress")]
+#[macro_export]
+macro_rules! shader_compressed {
+    ($file:expr) => ({
+        static CACHE: $crate::compress::Cache = $crate::compress::Cache::new(
+            include_bytes!(concat!(env!("OUT_DIR"), "/", $file, ".deflate")));
+        CACHE.get()
+    })
+}
@@ -0,0 +1,104 @@
+//! Rewriting of the locations reported in GPU driver info logs.
+//!
+//! Drivers report errors by source string number and line, which glossy sets with `#line`
+//! directives to the id of the original file and its line number. The functions in this module
+//! replace the ids with the file names, so logs can be read without looking up every id.
+
+/// Rewrites the locations in a driver info log so that they refer to file names instead of file
+/// ids, using `id_to_name` to look up the names. The names are usually those in the table generated
+/// by the build script, as used by `files::FileMap::translate_log()`.
+///
+/// Locations with ids that `id_to_name` does not know are left unchanged.
+pub fn rewrite_file_ids<'a, F>(log: &str, id_to_name: F) -> String
+    where F: Fn(u32) -> Option<&'a str>
+{
+    rewrite_locations(log, |id, line| id_to_name(id).map(|name| (name, line)))
+}
+
+/// Rewrites every location in a driver info log with `f`, which maps a source string number and
+/// line number to a file name and line number. Locations for which `f` returns `None` are left
+/// unchanged.
+///
+/// The formats recognized are `0(12)` (NVIDIA), `0:12(3)` (Mesa, with a column number) and
+/// `0:12:` (AMD, Intel on Windows, Apple and ANGLE).
+pub fn rewrite_locations<'a, F>(log: &str, mut f: F) -> String
+    where F: FnMut(u32, u32) -> Option<(&'a str, u32)>
+{
+    let bytes = log.as_bytes();
+    let mut output = String::with_capacity(log.len());
+    let mut copied = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let at_boundary = idx == 0 || !is_word_byte(bytes[idx - 1]);
+        if at_boundary && bytes[idx].is_ascii_digit() {
+            if let Some(location) = parse_location(&log[idx..]) {
+                if let Some((name, line)) = f(location.source, location.line) {
+                    output.push_str(&log[copied..idx]);
+                    output.push_str(name);
+                    output.push_str(&log[idx + location.separator.0..idx + location.separator.1]);
+                    output.push_str(&line.to_string());
+                    copied = idx + location.end;
+                }
+                idx += location.end;
+                continue;
+            }
+        }
+        idx += 1;
+    }
+    output.push_str(&log[copied..]);
+    output
+}
+
+/// A location at the start of a string.
+struct Location {
+    source: u32,
+    line: u32,
+    /// The byte range between the source string number and the line number
+    separator: (usize, usize),
+    /// The byte offset of the end of the line number
+    end: usize,
+}
+
+/// Parses a location at the start of `s`, in any of the recognized formats.
+fn parse_location(s: &str) -> Option<Location> {
+    let source_end = digits_len(s);
+    let source = s[..source_end].parse().ok()?;
+    let rest = &s[source_end..];
+
+    let (line_start, closing) = if rest.starts_with('(') {
+        // NVIDIA: 0(12)
+        (source_end + 1, Some(')'))
+    } else if rest.starts_with(':') {
+        // Mesa: 0:12(3), others: 0:12:
+        (source_end + 1, None)
+    } else {
+        return None;
+    };
+    let line_end = line_start + digits_len(&s[line_start..]);
+    let line = s[line_start..line_end].parse().ok()?;
+    let after = &s[line_end..];
+    let valid = match closing {
+        Some(closing) => after.starts_with(closing),
+        None => after.starts_with('(') || after.starts_with(':'),
+    };
+    if !valid {
+        return None;
+    }
+    Some(Location {
+        source: source,
+        line: line,
+        separator: (source_end, line_start),
+        end: line_end,
+    })
+}
+
+/// Returns the number of ASCII digits at the start of `s`.
+fn digits_len(s: &str) -> usize {
+    s.bytes().take_while(|b| b.is_ascii_digit()).count()
+}
+
+/// Returns true if a byte can be part of a word or number, in which case a location cannot start
+/// right after it.
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'.'
+}
@@ -0,0 +1,51 @@
+//! Obfuscation of the shader sources embedded in a binary.
+//!
+//! When `glossy_codegen::Config::obfuscate` is used, every shader source is also written in an
+//! obfuscated form, which can be embedded and decoded at runtime with the `shader_obfuscated!`
+//! macro. This keeps the sources from being read with tools like `strings`, but as the key is
+//! embedded in the same binary, it does not protect them from a determined reader.
+//!
+//! An obfuscated source consists of an 8 byte little-endian nonce, followed by the bytes of the
+//! source XORed with a keystream generated from the key and the nonce.
+
+/// Obfuscates a source with a key. The nonce is derived from the source itself, so that the
+/// output is the same across builds, while sources do not share a keystream.
+pub fn encode(source: &str, key: u64) -> Vec<u8> {
+    let nonce = source.bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64,
+              |hash, b| (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3));
+    let mut data = Vec::with_capacity(8 + source.len());
+    for idx in 0..8 {
+        data.push((nonce >> (idx * 8)) as u8);
+    }
+    data.extend(source.bytes());
+    apply_keystream(&mut data[8..], key ^ nonce);
+    data
+}
+
+/// Decodes a source obfuscated with `encode()`.
+///
+/// This panics if the data is too short to contain a nonce, or if the key is wrong such that the
+/// decoded source is not valid UTF-8.
+pub fn decode(data: &[u8], key: u64) -> String {
+    assert!(data.len() >= 8, "obfuscated shader source is too short");
+    let nonce = data[..8].iter().rev().fold(0u64, |nonce, &b| (nonce << 8) | b as u64);
+    let mut source = data[8..].to_vec();
+    apply_keystream(&mut source, key ^ nonce);
+    String::from_utf8(source).expect("obfuscated shader source decoded with the wrong key")
+}
+
+/// XORs data with the output of a SplitMix64 generator seeded with `seed`.
+fn apply_keystream(data: &mut [u8], seed: u64) {
+    let mut state = seed;
+    for chunk in data.chunks_mut(8) {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        for (idx, b) in chunk.iter_mut().enumerate() {
+            *b ^= (z >> (idx * 8)) as u8;
+        }
+    }
+}
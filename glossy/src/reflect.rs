@@ -0,0 +1,72 @@
+//! Types describing the interface of an optimized shader.
+//!
+//! These are generated by glossy_codegen from the glsl-optimizer reflection API and are accessed
+//! with the `shader_info!` macro.
+
+/// The basic type of a shader variable, as reported by glsl-optimizer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BasicType {
+    Float,
+    Int,
+    Bool,
+    Texture2D,
+    Texture3D,
+    TextureCube,
+    Texture2DShadow,
+    Texture2DArray,
+    Other,
+}
+
+/// A GLSL precision qualifier.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Precision {
+    High,
+    Medium,
+    Low,
+}
+
+/// A single input, uniform or texture used by an optimized shader.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Variable {
+    /// The name of the variable as declared in the shader source.
+    pub name: &'static str,
+    /// The basic (component) type of the variable.
+    pub ty: BasicType,
+    /// The precision of the variable. This is always `High` for desktop OpenGL.
+    pub precision: Precision,
+    /// The number of vector components, or columns for matrix types.
+    pub vec_size: u32,
+    /// The number of matrix rows, or 1 for non-matrix types.
+    pub mat_size: u32,
+    /// The number of array elements if the variable is an array.
+    pub array_size: Option<u32>,
+    /// The explicit or assigned location of the variable, if any.
+    pub location: Option<u32>,
+}
+
+/// The inputs, uniforms and textures which remain in a shader after optimization.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ShaderInfo {
+    pub inputs: &'static [Variable],
+    pub uniforms: &'static [Variable],
+    pub textures: &'static [Variable],
+    /// The total size of all uniforms, in floats.
+    pub uniform_total_size: u32,
+}
+
+impl ShaderInfo {
+    /// Returns the input with the given name, if it is used by the shader.
+    pub fn input(&self, name: &str) -> Option<&'static Variable> {
+        self.inputs.iter().find(|v| v.name == name)
+    }
+
+    /// Returns the uniform with the given name, if it is used by the shader.
+    pub fn uniform(&self, name: &str) -> Option<&'static Variable> {
+        self.uniforms.iter().find(|v| v.name == name)
+    }
+
+    /// Returns the texture with the given name, if it is used by the shader.
+    pub fn texture(&self, name: &str) -> Option<&'static Variable> {
+        self.textures.iter().find(|v| v.name == name)
+    }
+}
@@ -0,0 +1,62 @@
+//! Mapping of the lines of processed shader sources back to the files and lines they came from.
+//!
+//! A source map is generated for every shader by `glossy_codegen`, and can be accessed with the
+//! `shader_source_map!` macro.
+
+use log;
+
+/// A map from each line of a processed shader source to the original file and line it came from.
+#[derive(Copy, Clone, Debug)]
+pub struct SourceMap {
+    /// The ids and names of the files which make up the shader. The ids are those used by the
+    /// `#line` directives of the shader and by `files::FileMap`.
+    pub files: &'static [(u32, &'static str)],
+    /// The file id and line number, starting from 1, of each line of the processed source, or
+    /// `None` for lines which glossy generated itself. Lines of minified shaders map to the line of
+    /// their first token. This is empty for shaders optimized with glsl-optimizer, as it does not
+    /// preserve any correspondence between its input and output lines.
+    pub lines: &'static [Option<(u32, u32)>],
+    /// True if the processed source contains `#line` directives, in which case compilers report
+    /// errors at the original lines already.
+    pub line_directives: bool,
+}
+
+impl SourceMap {
+    /// Returns the name of the file with the given id.
+    pub fn file_name(&self, id: u32) -> Option<&'static str> {
+        self.files.iter().find(|&&(file_id, _)| file_id == id).map(|&(_, name)| name)
+    }
+
+    /// Returns the original file name and line number of a line of the processed source. Lines
+    /// are numbered from 1.
+    pub fn lookup(&self, line: u32) -> Option<(&'static str, u32)> {
+        if line == 0 {
+            return None;
+        }
+        match self.lines.get(line as usize - 1) {
+            Some(&Some((id, line))) => self.file_name(id).map(|name| (name, line)),
+            _ => None,
+        }
+    }
+
+    /// Rewrites the locations in a driver info log for this shader so that they refer to the
+    /// original file names and line numbers.
+    ///
+    /// If the shader has no `#line` directives, e.g. because it was built with
+    /// `discard_line_info()`, the locations the driver reports are lines of the processed source,
+    /// which are looked up in the map. Otherwise, they are already original line numbers, and
+    /// only the file ids are replaced with names. Locations which cannot be mapped are left
+    /// unchanged. The log formats of the common drivers are recognized, e.g. `0(12)`, `0:12(3)`
+    /// and `0:12:`.
+    pub fn translate_log(&self, log: &str) -> String {
+        log::rewrite_locations(log, |source, line| {
+            if self.line_directives {
+                self.file_name(source).map(|name| (name, line))
+            } else if source == 0 {
+                self.lookup(line)
+            } else {
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,260 @@
+//! The `glossy` command line tool, which preprocesses and validates shaders outside of cargo.
+//!
+//! ```text
+//! glossy build --lang gles30 --include 'inc/*' shaders/*.frag -o out/
+//! glossy check --lang gles30 --include 'inc/*' shaders/*.frag
+//! glossy preprocess --include 'inc/*' file.frag
+//! ```
+//!
+//! Every subcommand processes the shaders exactly as `glossy_codegen::Config::build()` does in a
+//! build script. Shaders with the extension "vert" or "frag" are loaded as vertex and fragment
+//! shaders respectively, as with `Config::source()`.
+
+extern crate clap;
+extern crate glob;
+extern crate glossy_codegen;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use glob::glob;
+use glossy_codegen::{Config, Language};
+
+fn main() {
+    let languages = languages();
+    let matches = App::new("glossy")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Preprocesses and validates GLSL shaders with #include support.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(shader_args(&languages, SubCommand::with_name("build")
+                .about("Processes shaders and writes them to a directory")
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .value_name("DIR")
+                    .help("The directory to write the processed shaders to")
+                    .required(true))
+                .arg(Arg::with_name("target")
+                    .short("t")
+                    .long("target")
+                    .value_name("LANG")
+                    .help("An additional language to build the shaders for, in a subdirectory")
+                    .possible_values(&languages)
+                    .multiple(true)
+                    .number_of_values(1))
+                .arg(Arg::with_name("FILES")
+                    .help("The shader sources, or glob patterns matching them")
+                    .required(true)
+                    .multiple(true))))
+        .subcommand(shader_args(&languages, SubCommand::with_name("check")
+                .about("Processes shaders to report any errors, without writing them")
+                .arg(Arg::with_name("FILES")
+                    .help("The shader sources, or glob patterns matching them")
+                    .required(true)
+                    .multiple(true))))
+        .subcommand(shader_args(&languages, SubCommand::with_name("preprocess")
+                .about("Prints the processed source of a shader")
+                .arg(Arg::with_name("FILE")
+                    .help("The shader source")
+                    .required(true))))
+        .get_matches();
+
+    match matches.subcommand() {
+        ("build", Some(matches)) => {
+            let out_path = Path::new(matches.value_of("output").unwrap());
+            if let Err(err) = fs::create_dir_all(out_path) {
+                fail(&format!("cannot create directory \"{}\": {}", out_path.display(), err));
+            }
+            let targets: Vec<Language> = matches.values_of("target")
+                .map(|values| values.map(language).collect())
+                .unwrap_or_default();
+            let config = config(matches, matches.values_of("FILES").unwrap().collect())
+                .targets(&targets)
+                .out_dir(out_path);
+            if let Err(err) = config.try_build() {
+                fail(&err);
+            }
+        }
+        ("check", Some(matches)) => {
+            let config = config(matches, matches.values_of("FILES").unwrap().collect());
+            if let Err(err) = build_temporary(config, |_| ()) {
+                fail(&err);
+            }
+        }
+        ("preprocess", Some(matches)) => {
+            let file = matches.value_of("FILE").unwrap();
+            let name = match Path::new(file).file_name() {
+                Some(name) => name.to_owned(),
+                None => fail(&format!("\"{}\" is not a file", file)),
+            };
+            let config = config(matches, vec![file]);
+            let result = build_temporary(config, |out_path| read_file(&out_path.join(&name)));
+            match result {
+                Ok(Ok(source)) => print!("{}", source),
+                Ok(Err(err)) => fail(&format!("cannot read processed shader: {}", err)),
+                Err(err) => fail(&err),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Helper function which returns the `short_name()` of every language glossy can target.
+fn languages() -> Vec<&'static str> {
+    Language::all().iter().map(|lang| lang.short_name()).collect()
+}
+
+/// Helper function which adds the arguments shared by every subcommand.
+fn shader_args<'a, 'b>(languages: &[&'b str], app: App<'a, 'b>) -> App<'a, 'b> {
+    let app = app.arg(Arg::with_name("lang")
+            .short("l")
+            .long("lang")
+            .value_name("LANG")
+            .help("The language to build the shaders for")
+            .possible_values(languages)
+            .default_value("gl"))
+        .arg(Arg::with_name("include")
+            .short("i")
+            .long("include")
+            .value_name("PATTERN")
+            .help("A glob pattern matching files which the shaders may #include")
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name("translate-from")
+            .long("translate-from")
+            .value_name("LANG")
+            .help("The language the shaders are written in, if they need translating")
+            .possible_values(languages));
+    optimizer_args(app)
+}
+
+/// Helper function which adds the optimizer arguments for the enabled features.
+fn optimizer_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    let app = if cfg!(feature = "optimizer") {
+        app.arg(Arg::with_name("optimize")
+            .short("O")
+            .long("optimize")
+            .help("Optimizes the shaders with glsl-optimizer"))
+    } else {
+        app
+    };
+    let app = if cfg!(feature = "minifier") {
+        app.arg(Arg::with_name("minify")
+            .long("minify")
+            .help("Optimizes the shaders with the minifier"))
+    } else {
+        app
+    };
+    if cfg!(any(feature = "optimizer", feature = "minifier")) {
+        app.arg(Arg::with_name("rename")
+            .long("rename")
+            .help("Shortens the names of local variables and functions when optimizing"))
+    } else {
+        app
+    }
+}
+
+/// Helper function which creates the configuration for the arguments of a subcommand.
+fn config(matches: &ArgMatches, files: Vec<&str>) -> Config {
+    let mut config = Config::new(language(matches.value_of("lang").unwrap()));
+    if let Some(patterns) = matches.values_of("include") {
+        for pattern in patterns {
+            check_pattern(pattern);
+            config = config.include(pattern);
+        }
+    }
+    for pattern in files {
+        check_pattern(pattern);
+        config = config.source(pattern);
+    }
+    if let Some(lang) = matches.value_of("translate-from") {
+        config = config.translate_from(language(lang));
+    }
+    configure_optimizer(matches, config)
+}
+
+/// Helper function which enables the optimizer requested by the arguments.
+#[cfg(any(feature = "optimizer", feature = "minifier"))]
+fn configure_optimizer(matches: &ArgMatches, config: Config) -> Config {
+    use glossy_codegen::{OptimizerBackend, OptimizerOptions};
+
+    let mut backend = None;
+    #[cfg(feature = "optimizer")]
+    {
+        if matches.is_present("optimize") {
+            backend = Some(OptimizerBackend::GlslOptimizer);
+        }
+    }
+    #[cfg(feature = "minifier")]
+    {
+        if matches.is_present("minify") {
+            backend = Some(OptimizerBackend::Minifier);
+        }
+    }
+    match backend {
+        Some(backend) => {
+            let options = OptimizerOptions::new().rename_identifiers(matches.is_present("rename"));
+            config.optimizer_backend(backend).optimizer_options(options)
+        }
+        None => config,
+    }
+}
+
+/// Helper function which enables the optimizer requested by the arguments (dummy).
+#[cfg(not(any(feature = "optimizer", feature = "minifier")))]
+fn configure_optimizer(_matches: &ArgMatches, config: Config) -> Config {
+    config
+}
+
+/// Helper function which returns the language with a `short_name()` accepted by clap.
+fn language(name: &str) -> Language {
+    Language::from_short_name(name).unwrap()
+}
+
+/// Helper function which fails if a file or glob pattern given on the command line matches
+/// nothing, as `Config` silently ignores such patterns.
+fn check_pattern(pattern: &str) {
+    match glob(pattern) {
+        Ok(mut paths) => {
+            if !paths.any(|path| path.is_ok()) {
+                fail(&format!("no files match \"{}\"", pattern));
+            }
+        }
+        Err(err) => fail(&format!("invalid pattern \"{}\": {}", pattern, err)),
+    }
+}
+
+/// Helper function which builds the shaders to a temporary directory, and calls `f` with the
+/// directory before removing it.
+fn build_temporary<T, F>(config: Config, f: F) -> Result<T, String>
+    where F: FnOnce(&Path) -> T
+{
+    let out_path = temporary_dir();
+    if let Err(err) = fs::create_dir_all(&out_path) {
+        fail(&format!("cannot create directory \"{}\": {}", out_path.display(), err));
+    }
+    let result = config.out_dir(&out_path).try_build().map(|()| f(&out_path));
+    fs::remove_dir_all(&out_path).ok();
+    result
+}
+
+/// Helper function which returns a directory for the shaders which are not kept.
+fn temporary_dir() -> PathBuf {
+    env::temp_dir().join(format!("glossy-{}", process::id()))
+}
+
+/// Helper function which reads the contents of a file as a string.
+fn read_file(path: &Path) -> io::Result<String> {
+    let mut s = String::new();
+    File::open(path)?.read_to_string(&mut s)?;
+    Ok(s)
+}
+
+/// Helper function which reports an error and exits.
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1)
+}
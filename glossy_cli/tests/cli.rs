@@ -0,0 +1,62 @@
+use std::env;
+use std::fs;
+use std::process::{Command, Output};
+
+/// Runs the `glossy` binary from the directory of the codegen tests, to share their shaders.
+fn glossy(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_glossy"))
+        .args(args)
+        .current_dir("../glossy_codegen")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn build() {
+    let out_dir = env::temp_dir().join("glossy_cli_build");
+    fs::remove_dir_all(&out_dir).ok();
+
+    let output = glossy(&["build",
+                          "--lang",
+                          "gl",
+                          "--target",
+                          "gles30",
+                          "--translate-from",
+                          "gl",
+                          "--include",
+                          "tests/include*.glsl",
+                          "tests/targets.frag",
+                          "-o",
+                          out_dir.to_str().unwrap()]);
+    assert!(output.status.success());
+    let read = |path: &str| fs::read_to_string(out_dir.join(path)).unwrap();
+    assert!(read("targets.frag").starts_with("#version 120\n"));
+    assert!(read("gles30/targets.frag").starts_with("#version 300 es\n"));
+    assert!(read("glossy_files.rs").contains("\"targets.frag\""));
+    fs::remove_dir_all(&out_dir).ok();
+}
+
+#[test]
+fn check() {
+    let output = glossy(&["check", "--include", "tests/include*.glsl", "tests/common.glsl"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = glossy(&["check", "--lang", "gl4", "tests/badversion.frag"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: shader source \"badversion.frag\" specifies version 120"));
+
+    let output = glossy(&["check", "tests/missing.frag"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn preprocess() {
+    let output = glossy(&["preprocess", "--include", "tests/include*.glsl", "tests/common.glsl"]);
+    assert!(output.status.success());
+    let source = String::from_utf8(output.stdout).unwrap();
+    assert!(source.starts_with("#version 120\n"));
+    assert!(!source.lines().any(|line| line.starts_with("#include")));
+    assert!(source.contains("float common_func()"));
+}
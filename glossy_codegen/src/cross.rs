@@ -0,0 +1,76 @@
+//! Compilation of processed shader sources to other shader formats with naga.
+//!
+//! naga's GLSL frontend only supports the Vulkan dialect of GLSL 4.40 and above. Errors are
+//! reported at the original file and line, using the source map of the processed source.
+
+use naga;
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use optimize::SourceKind;
+use output::SourceMap;
+
+/// Compiles a processed shader source to SPIR-V.
+#[cfg(feature = "spirv")]
+pub fn spirv(name: &str,
+             source: &str,
+             kind: SourceKind,
+             source_map: &SourceMap)
+             -> Result<Vec<u32>, String> {
+    let (module, info) = parse(name, source, kind, source_map)?;
+    naga::back::spv::write_vec(&module, &info, &naga::back::spv::Options::default(), None)
+        .map_err(|err| format!("{}: {}", name, err))
+}
+
+/// Translates a processed shader source to WGSL.
+#[cfg(feature = "wgsl")]
+pub fn wgsl(name: &str,
+            source: &str,
+            kind: SourceKind,
+            source_map: &SourceMap)
+            -> Result<String, String> {
+    let (module, info) = parse(name, source, kind, source_map)?;
+    naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+        .map_err(|err| format!("{}: {}", name, err))
+}
+
+/// Parses and validates a processed shader source.
+fn parse(name: &str,
+         source: &str,
+         kind: SourceKind,
+         source_map: &SourceMap)
+         -> Result<(naga::Module, ModuleInfo), String> {
+    let stage = match kind {
+        SourceKind::Vertex => naga::ShaderStage::Vertex,
+        SourceKind::Fragment => naga::ShaderStage::Fragment,
+        SourceKind::Unknown => {
+            return Err(format!("{}: only vertex and fragment shaders can be compiled", name))
+        }
+    };
+
+    let module = Frontend::default()
+        .parse(&Options::from(stage), source)
+        .map_err(|errors| {
+            let errors: Vec<String> = errors.iter()
+                .map(|err| {
+                    let line = err.meta.location(source).line_number;
+                    locate(name, source_map, Some(line), &err.to_string())
+                })
+                .collect();
+            errors.join("\n")
+        })?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| {
+            let line = err.location(source).map(|location| location.line_number);
+            locate(name, source_map, line, &err.as_inner().to_string())
+        })?;
+    Ok((module, info))
+}
+
+/// Formats an error message with the original location of a line of the processed source.
+fn locate(name: &str, source_map: &SourceMap, line: Option<u32>, message: &str) -> String {
+    match line.and_then(|line| source_map.lookup(line as usize)) {
+        Some(origin) => format!("{}:{}: {}", origin.file, origin.line, message),
+        None => format!("{}: {}", name, message),
+    }
+}
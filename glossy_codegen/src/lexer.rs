@@ -0,0 +1,207 @@
+//! A lexer for GLSL preprocessing tokens.
+//!
+//! A source is first split into logical lines, as in the first phases of the C preprocessor which
+//! GLSL follows: line continuations (a backslash at the end of a line) are spliced, and each
+//! comment is replaced by a single space. A block comment which spans several physical lines
+//! therefore joins them into one logical line. The code of each logical line can then be split
+//! into preprocessing tokens, which is how directives are recognized.
+
+/// A logical line of a source.
+pub struct Line<'a> {
+    /// The number of the first physical line of this logical line, starting from 1.
+    pub number: usize,
+    /// The number of physical lines which make up this logical line.
+    pub span: usize,
+    /// The original text of the physical lines, without the final line terminator.
+    pub text: &'a str,
+    /// The text with comments replaced by spaces and line continuations removed.
+    pub code: String,
+}
+
+impl<'a> Line<'a> {
+    /// Returns true if the line contains nothing but whitespace and comments.
+    pub fn is_empty(&self) -> bool {
+        self.code.trim().is_empty()
+    }
+
+    /// Returns the directive on this line, if it is a preprocessor directive.
+    pub fn directive<'b>(&'b self) -> Option<Directive<'b>> {
+        let code = self.code.trim_start();
+        if !code.starts_with('#') {
+            return None;
+        }
+        let rest = code[1..].trim_start();
+        let name_len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        Some(Directive {
+            name: name,
+            tokens: tokenize(&rest[name_len..], name == "include"),
+        })
+    }
+}
+
+/// A preprocessor directive, such as `#include "file.glsl"`.
+pub struct Directive<'a> {
+    /// The name of the directive, which is empty for the null directive `#`.
+    pub name: &'a str,
+    /// The tokens following the name of the directive.
+    pub tokens: Vec<Token<'a>>,
+}
+
+/// A GLSL preprocessing token.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Token<'a> {
+    Ident(&'a str),
+    Number(&'a str),
+    /// A quoted string, without the quotes. GLSL itself has no strings, but they are used by
+    /// `#include` and by `#line` with `GL_GOOGLE_cpp_style_line_directive`.
+    Str(&'a str),
+    /// An angle-bracketed header name, without the brackets, as used by `#include`.
+    Header(&'a str),
+    Punct(&'a str),
+}
+
+/// Punctuators which are longer than one character, longest first.
+const PUNCTUATORS: &[&str] = &["<<=", ">>=", "##", "++", "--", "<<", ">>", "<=", ">=", "==",
+                               "!=", "&&", "||", "^^", "+=", "-=", "*=", "/=", "%=", "&=",
+                               "|=", "^="];
+
+/// Splits a source into logical lines.
+pub fn lines<'a>(source: &'a str) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let mut number = 1;
+
+    while chars.peek().is_some() {
+        let start = chars.peek().unwrap().0;
+        let first = number;
+        let mut end = source.len();
+        let mut code = String::new();
+        let mut block_comment = false;
+        let mut line_comment = false;
+
+        while let Some((idx, c)) = chars.next() {
+            let next = chars.peek().map(|&(_, c)| c);
+            match c {
+                // Line continuation
+                '\\' if next == Some('\n') || next == Some('\r') => {
+                    if next == Some('\r') {
+                        chars.next();
+                    }
+                    if chars.peek().map(|&(_, c)| c) == Some('\n') {
+                        chars.next();
+                    }
+                    number += 1;
+                }
+                // Line terminator
+                '\n' | '\r' => {
+                    if c == '\r' && next == Some('\n') {
+                        chars.next();
+                    }
+                    number += 1;
+                    if block_comment {
+                        continue;
+                    }
+                    end = idx;
+                    break;
+                }
+                _ if block_comment => {
+                    if c == '*' && next == Some('/') {
+                        chars.next();
+                        block_comment = false;
+                        code.push(' ');
+                    }
+                }
+                _ if line_comment => {}
+                '/' if next == Some('/') => {
+                    chars.next();
+                    line_comment = true;
+                    code.push(' ');
+                }
+                '/' if next == Some('*') => {
+                    chars.next();
+                    block_comment = true;
+                }
+                _ => code.push(c),
+            }
+        }
+
+        lines.push(Line {
+            number: first,
+            span: if end == source.len() { number - first + 1 } else { number - first },
+            text: &source[start..end],
+            code: code,
+        });
+    }
+    lines
+}
+
+/// Replaces every comment in a source with whitespace and splices line continuations, while
+/// keeping every logical line on the same line number.
+pub fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    for line in lines(source) {
+        output.push_str(&line.code);
+        for _ in 0..line.span {
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Splits the code of a logical line into preprocessing tokens. If `header` is true, text
+/// enclosed in angle brackets is read as a header name.
+pub fn tokenize<'a>(code: &'a str, header: bool) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut rest = code.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        let second = rest[c.len_utf8()..].chars().next();
+        let (token, len) = if is_ident_char(c) && !c.is_ascii_digit() {
+            let len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+            (Token::Ident(&rest[..len]), len)
+        } else if c.is_ascii_digit() || (c == '.' && second.is_some_and(|c| c.is_ascii_digit())) {
+            let len = number_len(rest);
+            (Token::Number(&rest[..len]), len)
+        } else if c == '"' || (c == '<' && header) {
+            let close = if c == '"' { '"' } else { '>' };
+            match rest[1..].find(close) {
+                Some(idx) => {
+                    let contents = &rest[1..idx + 1];
+                    if c == '"' {
+                        (Token::Str(contents), idx + 2)
+                    } else {
+                        (Token::Header(contents), idx + 2)
+                    }
+                }
+                None => (Token::Punct(&rest[..1]), 1),
+            }
+        } else {
+            let len = PUNCTUATORS.iter()
+                .find(|p| rest.starts_with(*p))
+                .map_or(c.len_utf8(), |p| p.len());
+            (Token::Punct(&rest[..len]), len)
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    tokens
+}
+
+/// Returns the length of the number at the start of `s`, including any exponent and suffix.
+fn number_len(s: &str) -> usize {
+    let mut prev = ' ';
+    for (idx, c) in s.char_indices() {
+        let exponent_sign = (c == '+' || c == '-') && (prev == 'e' || prev == 'E') &&
+                            !s.starts_with("0x") && !s.starts_with("0X");
+        if !(is_ident_char(c) || c == '.' || exponent_sign) {
+            return idx;
+        }
+        prev = c;
+    }
+    s.len()
+}
+
+fn is_ident_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
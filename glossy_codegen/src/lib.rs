@@ -3,37 +3,112 @@
 //! Refer to the [glossy](../glossy/index.html) module for more details.
 
 extern crate glob;
+extern crate glossy;
 extern crate regex;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(any(feature = "spirv", feature = "wgsl"))]
+extern crate naga;
 
+#[cfg(any(feature = "spirv", feature = "wgsl"))]
+mod cross;
+mod lexer;
 mod optimize;
+mod output;
+mod transform;
+mod translate;
+mod version;
+mod webgl;
 
+use std::cmp;
 use std::env;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use glob::glob;
+use lexer::Token;
 use regex::Regex;
-use optimize::{Optimizer, SourceKind};
-
-/// The language that glossy will target when optimizing shaders.
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub enum Language {
-    OpenGl,
-    OpenGlEs20,
-    OpenGlEs30,
+use optimize::ShaderInfo;
+use output::{Origin, Output, SourceMap};
+use version::{Profile, Version};
+
+pub use optimize::{OptimizerBackend, SourceKind};
+#[cfg(any(feature = "optimizer", feature = "minifier"))]
+pub use optimize::OptimizerOptions;
+pub use transform::{Diagnostic, Diagnostics, ShaderTransform};
+pub use glossy::Language;
+pub use glossy::reflect::Precision;
+
+/// The policy for `#version` directives in included files.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VersionPolicy {
+    /// Included files must specify the same version as the file including them, if any.
+    Strict,
+    /// Included files must not specify a newer version than the file including them. This allows
+    /// libraries written for e.g. `#version 100` to be included in `#version 300 es` shaders.
+    NotNewer,
+    /// Versions specified by included files are ignored.
+    Ignore,
+    /// Included files may specify any version, but a warning is emitted if it differs from the
+    /// version of the file including them.
+    Warn,
+}
+
+impl VersionPolicy {
+    /// Returns true if an included file of version `include` may be included in a file of
+    /// version `parent`.
+    fn allows(&self, include: Version, parent: Version) -> bool {
+        match *self {
+            VersionPolicy::Strict => {
+                include.number == parent.number &&
+                include.effective_profile() == parent.effective_profile()
+            }
+            VersionPolicy::NotNewer => {
+                // GLSL and GLSL ES versions are not comparable
+                include.is_es() == parent.is_es() && include.number <= parent.number
+            }
+            VersionPolicy::Ignore |
+            VersionPolicy::Warn => true,
+        }
+    }
+}
+
+/// The form of the `#line` directives which map a processed shader back to its original files.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LineDirectives {
+    /// `#line N ID`, where `ID` is a source string number which the generated
+    /// `glossy::files::FileMap` maps back to a file name. Every GLSL compiler understands this
+    /// form.
+    FileIds,
+    /// `#line N "name"`, with the name of the file itself, as allowed by the
+    /// `GL_GOOGLE_cpp_style_line_directive` extension. The extension is enabled in every shader
+    /// with this form, so it should only be used with drivers and tools which support it, such as
+    /// glslang. WebGL does not expose the extension, so WebGL targets always use `FileIds`.
+    FileNames,
 }
 
 /// Configuration for the glossy shader preprocessor.
 pub struct Config {
     lang: Language,
+    targets: Vec<Language>,
     sources: Vec<Source>,
     includes: HashMap<String, String>,
-    optimizer: Option<Optimizer>,
+    optimizer: Option<OptimizerBackend>,
+    optimizer_options: optimize::OptimizerOptions,
     preserve_line_info: bool,
     allow_untested: bool,
+    default_precision: Option<(Precision, Precision)>,
+    source_lang: Option<Language>,
+    version_policy: VersionPolicy,
+    line_directives: LineDirectives,
+    spirv: bool,
+    wgsl: bool,
+    transforms: Vec<Box<dyn ShaderTransform>>,
+    obfuscation_key: Option<u64>,
+    compress: bool,
+    archive: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
 }
 
 impl Config {
@@ -41,14 +116,38 @@ impl Config {
     pub fn new(lang: Language) -> Config {
         Config {
             lang: lang,
+            targets: Vec::new(),
             sources: Vec::new(),
             includes: HashMap::new(),
             optimizer: None,
+            optimizer_options: optimize::OptimizerOptions::new(),
             preserve_line_info: true,
             allow_untested: false,
+            default_precision: None,
+            source_lang: None,
+            version_policy: VersionPolicy::Strict,
+            line_directives: LineDirectives::FileIds,
+            spirv: false,
+            wgsl: false,
+            transforms: Vec::new(),
+            obfuscation_key: None,
+            compress: false,
+            archive: None,
+            out_dir: None,
         }
     }
 
+    /// Specify additional languages to build the shaders for.
+    ///
+    /// The shaders built for each of these languages are written to a subdirectory of `OUT_DIR`
+    /// named after the language's `short_name()`, alongside the shaders built for the language
+    /// passed to `new()`, which remain in `OUT_DIR` itself. The source for a particular target can
+    /// then be selected at runtime with `shader!("file", glossy::Language::...)`.
+    pub fn targets(mut self, langs: &[Language]) -> Config {
+        self.targets.extend_from_slice(langs);
+        self
+    }
+
     /// Specify a glob pattern which adds the matching files to the list of shader sources as
     /// vertex shader sources.
     pub fn vertex(mut self, pattern: &str) -> Config {
@@ -99,7 +198,8 @@ impl Config {
         for entry in glob(pattern).unwrap() {
             if let Ok(entry) = entry {
                 let fname = entry.file_name().unwrap().to_str().unwrap().to_string();
-                self.includes.insert(fname, read_file(entry));
+                let source = read_file(entry).unwrap_or_else(|err| panic!("{}", err));
+                self.includes.insert(fname, source);
             }
         }
         self
@@ -108,11 +208,34 @@ impl Config {
     /// Specify that the shaders should be optimized with glsl-optimizer. This is only available
     /// when the 'optimizer' feature is enabled (which is the default option).
     ///
+    /// For each optimized shader, a description of the inputs, uniforms and textures it uses is
+    /// also generated, which can be accessed with the `shader_info!` macro.
+    ///
     /// This also implicitly sets the `discard_line_info()` option.
     #[cfg(feature = "optimizer")]
-    pub fn optimize(mut self) -> Config {
+    pub fn optimize(self) -> Config {
+        self.optimizer_backend(OptimizerBackend::GlslOptimizer)
+    }
+
+    /// Specify that the shaders should be optimized with the given backend. This is only
+    /// available when the 'optimizer' or 'minifier' feature is enabled.
+    ///
+    /// `OptimizerBackend::Minifier` is useful where glsl-optimizer cannot be built, or for
+    /// versions of GLSL it does not support. It does not generate the info for `shader_info!`.
+    ///
+    /// This also implicitly sets the `discard_line_info()` option.
+    #[cfg(any(feature = "optimizer", feature = "minifier"))]
+    pub fn optimizer_backend(mut self, backend: OptimizerBackend) -> Config {
         self.preserve_line_info = false;
-        self.optimizer = Some(Optimizer::new(self.lang));
+        self.optimizer = Some(backend);
+        self
+    }
+
+    /// Specify the options passed to the optimizer. This does not by itself enable optimization;
+    /// use `optimize()` or `optimizer_backend()` for that.
+    #[cfg(any(feature = "optimizer", feature = "minifier"))]
+    pub fn optimizer_options(mut self, options: OptimizerOptions) -> Config {
+        self.optimizer_options = options;
         self
     }
 
@@ -127,6 +250,140 @@ impl Config {
         self
     }
 
+    /// Specify the form of the `#line` directives which preserve the line and file information in
+    /// the shader. The default is `LineDirectives::FileIds`.
+    ///
+    /// The directives are generated so that every line of the processed shader is attributed to
+    /// the file and line it came from, taking into account that desktop GLSL before 3.30 numbers
+    /// lines after a `#line` directive differently from later versions and GLSL ES. They are
+    /// omitted entirely with `discard_line_info()`.
+    pub fn line_directives(mut self, directives: LineDirectives) -> Config {
+        self.line_directives = directives;
+        self
+    }
+
+    /// Specify the default float and int precisions of fragment shaders.
+    ///
+    /// GLSL ES fragment shaders have no default float precision, so shaders without a `precision`
+    /// statement fail to compile on real devices. When targeting a version of OpenGL ES, this
+    /// option inserts the corresponding `precision` statements directly after the `#version`
+    /// and `#extension` directives of every fragment shader. Precision statements in the shader
+    /// itself take precedence, as they appear later in the source. This option has no effect for
+    /// desktop OpenGL targets.
+    pub fn default_precision(mut self, float: Precision, int: Precision) -> Config {
+        self.default_precision = Some((float, int));
+        self
+    }
+
+    /// Specify that the shader sources are written for a language other than the target language,
+    /// and should be translated.
+    ///
+    /// After `#include` processing, sources are converted between the GLSL 1.10/1.20/ES 1.00
+    /// syntax (`attribute`, `varying`, `gl_FragColor`, `texture2D`) and the GLSL 1.30+/ES 3.00
+    /// syntax (`in`, `out`, a declared fragment output, `texture`) as required by the target, and
+    /// given a matching `#version` directive. Translating to `OpenGlEs20` yields `#version 100`,
+    /// to `OpenGlEs30` yields `#version 300 es` (and likewise for 3.1 and 3.2), to `OpenGl33Core`
+    /// and `OpenGl4x` yields `#version 330 core` and `#version 400 core`, and to `OpenGl` yields
+    /// `#version 120` or `#version 330` depending on the syntax of the source. Precision
+    /// qualifiers are removed when targeting GLSL 1.20; use `default_precision()` to add them when
+    /// targeting GLES.
+    ///
+    /// The translation is textual rather than a full compiler pass: interface variables must be
+    /// declared one per line, beginning with their storage qualifier, and fragment shaders may
+    /// declare at most one output.
+    pub fn translate_from(mut self, lang: Language) -> Config {
+        self.source_lang = Some(lang);
+        self
+    }
+
+    /// Specify which `#version` directives are allowed in included files.
+    ///
+    /// Included files which violate the policy cause a panic. Otherwise, the shader is compiled
+    /// with the version of the top-level file. The default is `VersionPolicy::Strict`.
+    pub fn version_policy(mut self, policy: VersionPolicy) -> Config {
+        self.version_policy = policy;
+        self
+    }
+
+    /// Specify that the shaders should also be compiled to SPIR-V, for use with Vulkan or OpenGL
+    /// 4.6. This is only available when the 'spirv' feature is enabled.
+    ///
+    /// The processed source of every vertex and fragment shader is compiled with naga before
+    /// optimization, and written to `OUT_DIR` with a `.spv` extension. The SPIR-V can then be
+    /// accessed with the `shader_spirv!` macro. naga only supports the Vulkan dialect of GLSL
+    /// 4.40 and above, so the shaders must be written in it. Compilation errors are reported at
+    /// the original file and line.
+    #[cfg(feature = "spirv")]
+    pub fn spirv(mut self) -> Config {
+        self.spirv = true;
+        self
+    }
+
+    /// Specify that the shaders should also be translated to WGSL, for use with wgpu. This is only
+    /// available when the 'wgsl' feature is enabled.
+    ///
+    /// The processed source of every vertex and fragment shader is translated with naga before
+    /// optimization, and written to `OUT_DIR` with a `.wgsl` extension. The WGSL can then be
+    /// accessed with the `shader_wgsl!` macro. As with `spirv()`, the shaders must be written in
+    /// the Vulkan dialect of GLSL 4.40 or above, and errors are reported at the original file and
+    /// line.
+    #[cfg(feature = "wgsl")]
+    pub fn wgsl(mut self) -> Config {
+        self.wgsl = true;
+        self
+    }
+
+    /// Add a pass over the processed shader sources, such as a custom validator or code generator.
+    ///
+    /// Transforms run in the order they are added, either before optimization or after it, as
+    /// chosen by `ShaderTransform::after_optimization`. If a transform fails, the build panics
+    /// with its diagnostics, located at the original files and lines where possible.
+    pub fn transform(mut self, transform: Box<dyn ShaderTransform>) -> Config {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Specify that the shaders should also be written in an obfuscated form, so that they can be
+    /// embedded in the binary without being readable as plain text.
+    ///
+    /// The obfuscated sources can be decoded at runtime with the `shader_obfuscated!` macro. See
+    /// the `glossy::obfuscate` module for the format, and for how much protection it offers.
+    pub fn obfuscate(mut self, key: u64) -> Config {
+        self.obfuscation_key = Some(key);
+        self
+    }
+
+    /// Specify that the shaders should also be written compressed, to reduce the size of binaries
+    /// which embed many shaders. This is only available when the 'compress' feature is enabled.
+    ///
+    /// The compressed sources can be embedded with the `shader_compressed!` macro, which requires
+    /// the 'compress' feature of glossy, and decompresses each source on first access.
+    #[cfg(feature = "compress")]
+    pub fn compress(mut self) -> Config {
+        self.compress = true;
+        self
+    }
+
+    /// Specify that every shader built for every language should also be written to a single
+    /// archive file, together with its stage, version, includes and reflection info, and the
+    /// table of file ids. A relative path is relative to `OUT_DIR`.
+    ///
+    /// The archive can be read at runtime with `glossy::archive::Archive::read()`, which also
+    /// documents the format.
+    pub fn archive<P: AsRef<Path>>(mut self, path: P) -> Config {
+        self.archive = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Specify the directory the processed shaders are written to, instead of `OUT_DIR`.
+    ///
+    /// This is for running glossy outside of a build script, such as from the `glossy` command
+    /// line tool: no cargo directives are printed, and warnings are written to stderr instead.
+    pub fn out_dir<P: AsRef<Path>>(mut self, path: P) -> Config {
+        self.out_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
     /// Allow the optimizer to work on untested language versions.
     ///
     /// According to the glsl-optimizer readme, versions of GLSL beyond 1.20 are untested. This flag
@@ -144,150 +401,406 @@ impl Config {
     /// compile-time shader validator to a certain extent. Note that the error messages generated
     /// by the compiler will have inaccurate file line information, as it does not account for the
     /// #included files or stripped comments (the optimizer does not support reading comments).
+    ///
+    /// Problems which do not prevent the shaders from being built, such as included files with a
+    /// different version under `VersionPolicy::Warn`, are reported as cargo warnings instead.
     pub fn build(self) {
-        use std::io::Write;
+        if let Err(err) = self.try_build() {
+            panic!("{}", err);
+        }
+    }
 
-        let out_dir = env::var("OUT_DIR").unwrap();
-        let out_path = Path::new(&out_dir);
+    /// Process the specified GLSL shaders, returning the error which `build()` would panic with.
+    ///
+    /// This is useful for tools which report errors themselves, such as the `glossy` command line
+    /// tool.
+    pub fn try_build(self) -> Result<(), String> {
+        let out_path = match self.out_dir {
+            Some(ref path) => path.clone(),
+            None => {
+                PathBuf::from(env::var("OUT_DIR")
+                    .map_err(|_| "OUT_DIR is not set, and no output directory was given")?)
+            }
+        };
+        let out_path = out_path.as_path();
 
-        // A map of include names to file IDs
-        let mut include_map = HashMap::new();
+        // A map of shader and include names to file IDs
+        let shader_names = self.sources
+            .iter()
+            .map(|source| source.path.file_name().unwrap().to_str().unwrap());
+        let file_ids = file_ids(shader_names.chain(self.includes.keys().map(|name| name.as_str())));
+
+        // Write each shader file for the main language, then for each additional target
+        let mut archive = Vec::new();
+        let shader_includes = self.build_target(self.lang, out_path, &file_ids, &mut archive)?;
+        for &lang in self.targets.iter() {
+            let target_path = out_path.join(lang.short_name());
+            fs::create_dir_all(&target_path).map_err(|err| {
+                    format!("cannot create directory \"{}\": {}", target_path.display(), err)
+                })?;
+            self.build_target(lang, &target_path, &file_ids, &mut archive)?;
+        }
 
-        // Write each shader file
+        // Write the target selection source file for each shader
         for shader_source in self.sources.iter() {
             let name = shader_source.path.file_name().unwrap().to_str().unwrap();
-            let source = read_file(&shader_source.path);
+            let mut targets = format!("|lang: ::glossy::Language| -> Option<&'static str> {{ \
+                                       match lang {{\n\
+                                       ::glossy::Language::{:?} => \
+                                       Some(include_str!(concat!(env!(\"OUT_DIR\"), \"/{}\"))),\n",
+                                      self.lang,
+                                      name);
+            for lang in self.targets.iter().filter(|&&lang| lang != self.lang) {
+                targets.push_str(&format!("::glossy::Language::{:?} => \
+                                           Some(include_str!(concat!(env!(\"OUT_DIR\"), \
+                                           \"/{}/{}\"))),\n",
+                                          lang,
+                                          lang.short_name(),
+                                          name));
+            }
+            targets.push_str("#[allow(unreachable_patterns)] _ => None } }");
+            write_file(&out_path.join(format!("{}.targets.rs", name)), targets)?;
+        }
+
+        // Write the archive
+        if let Some(ref path) = self.archive {
+            let mut files: Vec<(u32, String)> =
+                file_ids.iter().map(|(name, &id)| (id as u32, name.clone())).collect();
+            files.sort();
+            let archive = glossy::archive::Archive {
+                files: files,
+                shaders: archive,
+            };
+            let mut bytes = Vec::new();
+            archive.write(&mut bytes).unwrap();
+            write_file(&out_path.join(path), bytes)?;
+        }
+
+        // Write the file table
+        let mut table = String::from("::glossy::files::FileMap {\n    files: &[\n");
+        let mut file_ids: Vec<(String, usize)> = file_ids.into_iter().collect();
+        file_ids.sort_by_key(|&(_, ref v)| *v);
+        for (name, id) in file_ids.into_iter() {
+            table.push_str(&format!("        ({}, {:?}),\n", id, name));
+        }
+        table.push_str("    ],\n    shaders: &[\n");
+        for (name, includes) in shader_includes {
+            table.push_str(&format!("        ({:?}, &{:?}),\n", name, includes));
+        }
+        table.push_str("    ],\n}\n");
+        write_file(&out_path.join("glossy_files.rs"), table)?;
+
+        // Write the key for the obfuscated copies
+        if let Some(key) = self.obfuscation_key {
+            write_file(&out_path.join("glossy_obfuscation_key.rs"),
+                       format!("{:#018x}u64\n", key))?;
+        }
+
+        // Let the crate conditionally compile on the main language, when run from a build script
+        if self.out_dir.is_none() {
+            let names: Vec<String> = Language::all()
+                .iter()
+                .map(|lang| format!("{:?}", lang.short_name()))
+                .collect();
+            println!("cargo:rustc-check-cfg=cfg(glossy_lang, values({}))",
+                     names.join(", "));
+            println!("cargo:rustc-cfg=glossy_lang=\"{}\"", self.lang.short_name());
+        }
+        Ok(())
+    }
+
+    /// Helper function which processes and writes every shader source for one language, and
+    /// returns the name of each shader with the names of the files it includes. The shaders are
+    /// also added to `archive` if an archive is to be written.
+    fn build_target<'a>(&'a self,
+                        lang: Language,
+                        out_path: &Path,
+                        file_ids: &HashMap<String, usize>,
+                        archive: &mut Vec<glossy::archive::Shader>)
+                        -> Result<Vec<(&'a str, Vec<&'a str>)>, String> {
+        let optimizer = self.optimizer
+            .map(|backend| optimize::new_backend(backend, lang, self.optimizer_options));
+        let mut shader_includes = Vec::new();
+
+        for shader_source in self.sources.iter() {
+            let name = shader_source.path.file_name().unwrap().to_str().unwrap();
+            let source = read_file(&shader_source.path)?;
+
+            // WebGL restrictions, checked on the original files so errors point at them
+            if lang.is_webgl() {
+                let errors =
+                    webgl::validate(lang, shader_source.kind, name, &source, &self.includes);
+                if !errors.is_empty() {
+                    return Err(format!("WebGL validation failed:\n{}", errors.join("\n")));
+                }
+            }
 
             // #include
-            let (source, version) = self.process(&mut include_map,
-                                                 &name,
-                                                 source.trim_right(),
-                                                 Vec::new(),
-                                                 0,
-                                                 None);
+            let mut extensions = Vec::new();
+            let file = Inclusion {
+                name: name,
+                source: source.trim_end(),
+                file_id: file_ids[name],
+                include_stack: Vec::new(),
+                version: None,
+                conditional: false,
+            };
+            let (output, version) = self.process(lang, file_ids, file, &mut extensions)?;
+            shader_includes.push((name, output.includes().to_vec()));
+            let (source, mut source_map) = self.render(lang, name, output, version, extensions)?;
+
+            // translate
+            let (source, version) = match self.source_lang {
+                Some(from) if from != lang => {
+                    match translate::translate(&source,
+                                               shader_source.kind,
+                                               from,
+                                               lang,
+                                               version,
+                                               &mut source_map) {
+                        Ok(result) => result,
+                        Err(err) => {
+                            return Err(format!("translation error for shader source \"{}\": {}",
+                                               name,
+                                               err));
+                        }
+                    }
+                }
+                _ => (source, version),
+            };
+
+            // validate the version against the target
+            if !version_accepted(lang, version) {
+                return Err(format!("shader source \"{}\" specifies version {}, which is not \
+                                    supported by {:?}",
+                                   name,
+                                   version,
+                                   lang));
+            }
+
+            // precision
+            let source =
+                self.insert_default_precision(lang, source, shader_source.kind, &mut source_map);
+            if lang.is_webgl() {
+                if let Some(err) = webgl::validate_precision(shader_source.kind, name, &source) {
+                    return Err(format!("WebGL validation failed:\n{}", err));
+                }
+            }
+
+            // user transforms
+            let source = self.run_transforms(false,
+                                             lang,
+                                             name,
+                                             shader_source.kind,
+                                             source,
+                                             &mut source_map)?;
+
+            // SPIR-V, compiled from the source before optimization
+            if self.spirv && shader_source.kind != SourceKind::Unknown {
+                write_spirv(out_path, name, &source, shader_source.kind, &source_map)?;
+            }
+
+            // WGSL, translated from the source before optimization
+            if self.wgsl && shader_source.kind != SourceKind::Unknown {
+                write_wgsl(out_path, name, &source, shader_source.kind, &source_map)?;
+            }
 
             // optimize
-            let source = if self.version_supported(version) {
-                match self.do_optimize(source, shader_source.kind) {
-                    Ok(source) => source,
-                    Err(err) => {
-                        panic!("optimization error for shader source \"{}\": {}", name, err);
+            let (source, info) = match optimizer {
+                Some(ref optimizer) if self.allow_untested || optimizer.supports(lang, version) => {
+                    // Remember the explicitly-declared precisions to check them against the output
+                    let declared = declared_precisions(&source);
+                    match optimizer.optimize(source, shader_source.kind) {
+                        Ok(optimized) => {
+                            if let Some(ref info) = optimized.info {
+                                check_precisions(lang, name, &declared, info)?;
+                            }
+                            match optimized.lines {
+                                Some(ref lines) => source_map.rearrange(lines),
+                                // The lines cannot be traced back to the original files
+                                None => source_map = SourceMap::new(false),
+                            }
+                            (optimized.source, optimized.info)
+                        }
+                        Err(err) => {
+                            return Err(format!("optimization error for shader source \"{}\": {}",
+                                               name,
+                                               err));
+                        }
                     }
                 }
-            } else {
-                source
+                _ => (source, None),
             };
+            let source = self.run_transforms(true,
+                                             lang,
+                                             name,
+                                             shader_source.kind,
+                                             source,
+                                             &mut source_map)?;
 
             // write to file
-            let mut file = File::create(out_path.join(name)).unwrap();
-            file.write_all(source.as_bytes()).unwrap();
-        }
+            write_file(&out_path.join(name), &source)?;
+
+            // write the compressed copy
+            if self.compress {
+                write_compressed(out_path, name, &source)?;
+            }
+
+            // write the obfuscated copy
+            if let Some(key) = self.obfuscation_key {
+                write_file(&out_path.join(format!("{}.obf", name)),
+                           glossy::obfuscate::encode(&source, key))?;
+            }
+
+            // write the source map
+            let mut map = Vec::new();
+            source_map.write_rust(&mut map).unwrap();
+            write_file(&out_path.join(format!("{}.map.rs", name)), map)?;
+
+            // write the reflection info, if the optimizer provided any
+            if let Some(ref info) = info {
+                let mut rust = Vec::new();
+                info.write_rust(&mut rust).unwrap();
+                write_file(&out_path.join(format!("{}.info.rs", name)), rust)?;
+            }
 
-        // Write the map source file
-        let mut file = File::create(out_path.join("glossy_file_id_to_name.rs")).unwrap();
-        write!(&mut file,
-               "|id: u32| -> Option<&'static str> {{ match id {{\n")
-            .unwrap();
-        let mut include_map: Vec<(String, usize)> = include_map.into_iter().collect();
-        include_map.sort_by_key(|&(_, ref v)| *v);
-        for (name, id) in include_map.into_iter() {
-            write!(&mut file, "{} => Some({:?}),\n", id, name).unwrap();
+            // add to the archive
+            if self.archive.is_some() {
+                let includes = &shader_includes[shader_includes.len() - 1].1;
+                archive.push(glossy::archive::Shader {
+                    name: name.to_string(),
+                    lang: lang,
+                    stage: match shader_source.kind {
+                        SourceKind::Vertex => glossy::archive::Stage::Vertex,
+                        SourceKind::Fragment => glossy::archive::Stage::Fragment,
+                        SourceKind::Unknown => glossy::archive::Stage::Other,
+                    },
+                    version: version.to_string(),
+                    source: source,
+                    includes: includes.iter().map(|include| include.to_string()).collect(),
+                    info: info.map(|info| info.to_archive()),
+                });
+            }
         }
-        write!(&mut file, "_ => None }} }}").unwrap();
+        Ok(shader_includes)
     }
 
-    /// Helper function which returns a newly-generated shader source with inlined #includes
-    fn process(&self,
-               include_map: &mut HashMap<String, usize>,
-               name: &str,
-               source: &str,
-               include_stack: Vec<&str>,
-               file_id: usize,
-               version: Option<&str>)
-               -> (String, String) {
-        use std::fmt::Write;
-
-        lazy_static! {
-            static ref LINE_COMMENT_RE: Regex = Regex::new(r"/\*.*?\*/|//.*").unwrap();
-            static ref INCLUDE_ANGLE_RE: Regex = Regex::new("^\\s*#\\s*include\\s+<([:print:]+)>\\s*$").unwrap();
-            static ref INCLUDE_QUOTE_RE: Regex = Regex::new("^\\s*#\\s*include\\s+\"([:print:]+)\"\\s*$").unwrap();
-            static ref VERSION_RE: Regex = Regex::new(r"^\s*#\s*version\s+(\d+)").unwrap();
-            static ref EMPTY_RE: Regex = Regex::new(r"^\s*$").unwrap();
+    /// Helper function which runs the user transforms which run either before or after
+    /// optimization, in order.
+    fn run_transforms(&self,
+                      after_optimization: bool,
+                      lang: Language,
+                      name: &str,
+                      kind: SourceKind,
+                      mut source: String,
+                      source_map: &mut SourceMap)
+                      -> Result<String, String> {
+        let transforms = self.transforms
+            .iter()
+            .filter(|transform| transform.after_optimization() == after_optimization);
+        for transform in transforms {
+            match transform.transform(&source, kind, lang) {
+                Ok(transformed) => {
+                    // Transforms are required to change lines only in place, so the map only
+                    // becomes invalid if lines were added or removed
+                    if transformed.lines().count() != source.lines().count() {
+                        source_map.discard_lines();
+                    }
+                    source = transformed;
+                }
+                Err(diagnostics) => {
+                    let errors: Vec<String> = diagnostics.diagnostics
+                        .iter()
+                        .map(|diagnostic| {
+                            let origin = diagnostic.line.and_then(|line| source_map.lookup(line));
+                            match (origin, diagnostic.line) {
+                                (Some(origin), _) => {
+                                    format!("{}:{}: {}",
+                                            origin.file,
+                                            origin.line,
+                                            diagnostic.message)
+                                }
+                                (None, Some(line)) => {
+                                    format!("{}:{}: {}", name, line, diagnostic.message)
+                                }
+                                (None, None) => format!("{}: {}", name, diagnostic.message),
+                            }
+                        })
+                        .collect();
+                    return Err(format!("transform error for shader source \"{}\":\n{}",
+                                       name,
+                                       errors.join("\n")));
+                }
+            }
         }
+        Ok(source)
+    }
+
+    /// Helper function which returns a newly-generated shader source with inlined #includes, with
+    /// the origin of every line
+    fn process<'a, 'b>(&'a self,
+                       lang: Language,
+                       file_ids: &HashMap<String, usize>,
+                       file: Inclusion<'a, 'b>,
+                       extensions: &mut Vec<(String, String)>)
+                       -> Result<(Output<'a>, Version), String> {
+        let Inclusion { name, source, file_id, include_stack, version, conditional } = file;
 
         // The processed source
-        let mut output = String::new();
+        let mut output = Output::new();
+        // the depth of the conditional blocks the current line is in, within this file
+        let mut conditional_depth = 0usize;
 
-        // true if we're in a block comment
-        let mut block_comment = false;
         // true if we've yet to parse either the first line or the #version directive
         let mut first_line = true;
         // the version parsed in the source
-        let mut parsed_version = String::new();
-        // the first line num (for the line directive)
-        let mut first_line_num = 1;
-
-        for (line_num, line) in source.lines().enumerate() {
-            let line_after_block_comment = if block_comment {
-                // If we're in a block comment, look for the first terminator we can find
-                match line.find("*/") {
-                    Some(idx) => {
-                        block_comment = false;
-                        &line[idx + 2..]
-                    }
-                    None => {
-                        // If there's no terminator, continue along.
-                        if self.preserve_line_info {
-                            write!(&mut output, "{}\n", line).unwrap();
-                        }
-                        continue;
-                    }
-                }
-            } else {
-                &line
-            };
+        let mut parsed_version = version.unwrap_or_else(|| default_version(lang));
 
-            // Remove all single-line comments from this line for testing
-            let line_after_line_comments = LINE_COMMENT_RE.replace(line_after_block_comment, "");
-
-            // Deal with the start of multi-line block comment starters
-            let line_no_comments = match line_after_line_comments.find("/*") {
-                Some(idx) => {
-                    block_comment = true;
-                    &line_after_line_comments[..idx]
-                }
-                None => &line_after_line_comments,
+        for line in lexer::lines(source) {
+            let origin = Origin {
+                file_id: file_id,
+                file: name,
+                line: line.number,
             };
 
-            if EMPTY_RE.is_match(line_no_comments) {
+            if line.is_empty() {
                 // Skip empty lines if we're not preserving line info
-                if !self.preserve_line_info {
-                    continue;
+                if self.preserve_line_info {
+                    output.push(line.text, origin);
                 }
-            } else if first_line {
+                continue;
+            }
+
+            let directive = line.directive();
+            let directive_name = directive.as_ref().map(|directive| directive.name);
+            let tokens = directive.as_ref().map_or(&[][..], |directive| &directive.tokens[..]);
+
+            if first_line {
                 first_line = false;
                 // If this is the first non-empty line, check if it's a #version directive.
-                if let Some(cap) = VERSION_RE.captures(line_no_comments) {
-                    parsed_version = cap.at(1).unwrap().to_string();
-                    // If the version is different than the one passed in, panic
+                if directive_name == Some("version") {
+                    parsed_version = parse_version(tokens).ok_or_else(|| {
+                            format!("shader file \"{}\" has an invalid #version directive", name)
+                        })?;
+                    // If the version is not allowed by the policy, fail. Otherwise, the shader is
+                    // compiled with the version of the top-level file.
                     if let Some(version) = version {
-                        if parsed_version != version {
-                            panic!("included file \"{}\" specifies version {}, but parent \
-                                    specifies \"{}\"",
-                                   name,
-                                   parsed_version,
-                                   version);
+                        if !self.version_policy.allows(parsed_version, version) {
+                            return Err(format!("included file \"{}\" specifies version {}, but \
+                                                parent specifies version {}",
+                                               name,
+                                               parsed_version,
+                                               version));
                         }
-                    }
-                    // The first line is 2
-                    first_line_num = 2;
-                    // Write a compensatory blank line if necessary.
-                    if self.preserve_line_info && line_num > 0 {
-                        if block_comment {
-                            write!(&mut output, "/*\n").unwrap();
-                        } else {
-                            write!(&mut output, "\n").unwrap();
+                        if self.version_policy == VersionPolicy::Warn &&
+                           !VersionPolicy::Strict.allows(parsed_version, version) {
+                            self.warning(&format!("included file \"{}\" specifies version {}, \
+                                                   but parent specifies version {}",
+                                                  name,
+                                                  parsed_version,
+                                                  version));
                         }
+                        parsed_version = version;
                     }
                     // Since we know this is a version directive, skip the rest of the loop.
                     continue;
@@ -295,135 +808,164 @@ impl Config {
                     // The first line is 1, so don't touch it
 
                     // Default to our parent's version; if None, then the default.
-                    parsed_version = match version {
-                        Some(v) => v.to_string(),
-                        None => {
-                            match self.lang {
-                                Language::OpenGl => "110".to_string(),
-                                Language::OpenGlEs20 |
-                                Language::OpenGlEs30 => "100".to_string(),
-                            }
-                        }
-                    };
+                    parsed_version = version.unwrap_or_else(|| default_version(lang));
                 }
             }
 
-            if self.preserve_line_info {
-                // If the line is not empty, and we've not yet inserted a line directive,
-                // we can now do so.
-                if !first_line && EMPTY_RE.is_match(line_no_comments) {
+            match directive_name {
+                Some("if") | Some("ifdef") | Some("ifndef") => conditional_depth += 1,
+                Some("endif") => conditional_depth = conditional_depth.saturating_sub(1),
+                _ => {}
+            }
+            let in_conditional = conditional || conditional_depth > 0;
 
-                }
-            } else {
-                // If we don't care about line info, skip this line if it's empty
-                // OR if we're an included file and it's a #version directive
-                if EMPTY_RE.is_match(line_no_comments) {
+            let include_name = match directive_name {
+                // If we don't care about line info, skip #version directives in included files
+                Some("version") if !self.preserve_line_info && !include_stack.is_empty() => {
                     continue;
                 }
-                if !include_stack.is_empty() && VERSION_RE.is_match(line_no_comments) {
+                // #extension directives are hoisted to the top of the shader, as they must
+                // precede any non-preprocessor tokens. Conditional ones are left in place, as
+                // they may only apply to some implementations.
+                Some("extension") if !in_conditional => {
+                    match *tokens {
+                        [Token::Ident(extension), Token::Punct(":"), Token::Ident(behavior)] => {
+                            add_extension(extensions, name, extension, behavior)?;
+                        }
+                        _ => {
+                            return Err(format!("shader file \"{}\" has an invalid #extension \
+                                                directive on line {}",
+                                               name,
+                                               line.number));
+                        }
+                    }
+                    if self.preserve_line_info {
+                        output.push(&"\n".repeat(line.span - 1), origin);
+                    }
                     continue;
                 }
-            }
-
-            // Now actually figure out if this is an #include directive.
-            let include_name;
-            if let Some(cap) = INCLUDE_QUOTE_RE.captures(line_no_comments) {
-                include_name = cap.at(1).unwrap();
-            } else if let Some(cap) = INCLUDE_ANGLE_RE.captures(line_no_comments) {
-                include_name = cap.at(1).unwrap();
-            } else {
-                // Write the line and move on
-                if self.preserve_line_info {
-                    write!(&mut output, "{}\n", line).unwrap();
-                } else {
-                    write!(&mut output, "{}\n", line_no_comments).unwrap();
+                Some("include") => {
+                    match *tokens {
+                        [Token::Str(include_name)] |
+                        [Token::Header(include_name)] => include_name,
+                        _ => {
+                            return Err(format!("shader file \"{}\" has an invalid #include \
+                                                directive on line {}",
+                                               name,
+                                               line.number));
+                        }
+                    }
+                }
+                _ => {
+                    // Write the line and move on
+                    if self.preserve_line_info {
+                        output.push(line.text, origin);
+                    } else {
+                        output.push(&line.code, origin);
+                    }
+                    continue;
                 }
-                continue;
             };
 
             // This is an #include! So #include it.
-            let include_source = self.includes.get(include_name).unwrap_or_else(|| {
-                panic!("shader file \"{}\" includes non-existent file \"{}\"",
-                       name,
-                       include_name);
-            });
+            let (include_name, include_source) = self.includes
+                .get_key_value(include_name)
+                .ok_or_else(|| {
+                    format!("shader file \"{}\" includes non-existent file \"{}\"",
+                            name,
+                            include_name)
+                })?;
+            let include_name = include_name.as_str();
             // But first, see if we've already got it in our #include stack, and complain about
             // recursive includes.
             if include_stack.contains(&include_name) {
-                panic!("recursive inclusion of \"{}\" in shader file \"{}\"",
-                       include_name,
-                       name);
+                return Err(format!("recursive inclusion of \"{}\" in shader file \"{}\"",
+                                   include_name,
+                                   name));
             }
             // Process the included file
             let mut sub_include_stack = include_stack.clone();
             sub_include_stack.push(include_name);
-            let new_id = include_map.len() + 1;
-            let include_file_id = *include_map.entry(include_name.to_string()).or_insert(new_id);
-            let (include_source, _) = self.process(include_map,
-                                                   include_name,
-                                                   include_source,
-                                                   sub_include_stack,
-                                                   include_file_id,
-                                                   Some(&parsed_version));
-            if self.preserve_line_info {
-                write!(&mut output,
-                       "{}\n#line {} {}\n",
-                       include_source,
-                       line_num + 2,
-                       file_id)
-                    .unwrap();
-                if block_comment {
-                    // If there was a start of a block comment on this line,
-                    // we need to insert that here
-                    write!(&mut output, "/*\n").unwrap();
-                }
-            } else {
-                if !include_source.is_empty() {
-                    write!(&mut output, "{}\n", include_source).unwrap();
-                }
-            }
-        }
-        // Return
-        if include_stack.is_empty() {
-            (format!("#version {}\n#line {} {}\n{}",
-                     parsed_version,
-                     first_line_num,
-                     file_id,
-                     output.trim_right()),
-             parsed_version)
-        } else {
-            (format!("#line {} {}\n{}",
-                     first_line_num,
-                     file_id,
-                     output.trim_right()),
-             parsed_version)
+            let include_file = Inclusion {
+                name: include_name,
+                source: include_source,
+                file_id: file_ids[include_name],
+                include_stack: sub_include_stack,
+                version: Some(parsed_version),
+                conditional: in_conditional,
+            };
+            let (include_output, _) = self.process(lang, file_ids, include_file, extensions)?;
+            output.include(include_name, include_output);
         }
+        output.trim_end();
+        Ok((output, parsed_version))
     }
 
-    /// Helper function which determines if the optimizer supports the version of the
-    /// shader-to-be-optimized.
-    fn version_supported(&self, version: String) -> bool {
-        match self.lang {
-            Language::OpenGl => (self.allow_untested || version == "110" || version == "120"),
-            Language::OpenGlEs20 |
-            Language::OpenGlEs30 => (version == "100" || version == "300"),
-        }
+    /// Helper function which renders a processed shader source, preceded by its `#version` and
+    /// `#extension` directives, and returns it with its source map.
+    fn render<'a>(&self,
+                  lang: Language,
+                  name: &str,
+                  output: Output<'a>,
+                  version: Version,
+                  mut extensions: Vec<(String, String)>)
+                  -> Result<(String, SourceMap<'a>), String> {
+        let directives = match self.line_directives {
+            _ if !self.preserve_line_info => None,
+            LineDirectives::FileNames if lang.is_webgl() => Some(LineDirectives::FileIds),
+            LineDirectives::FileNames => {
+                add_extension(&mut extensions,
+                              name,
+                              "GL_GOOGLE_cpp_style_line_directive",
+                              "require")?;
+                Some(LineDirectives::FileNames)
+            }
+            directives => Some(directives),
+        };
+        // The directives are interpreted according to the version of the final shader
+        let final_version = match self.source_lang {
+            Some(from) if from != lang => translate::translated_version(from, lang, version),
+            _ => version,
+        };
+
+        let extensions: String = extensions.iter()
+            .map(|&(ref name, ref behavior)| format!("#extension {} : {}\n", name, behavior))
+            .collect();
+        let (body, mut source_map) = output.render(directives, final_version);
+        let source = format!("#version {}\n{}{}", version, extensions, body);
+        source_map.align(&source);
+        Ok((source, source_map))
     }
 
-    /// Helper function which optimizes the shader source with glsl-optimizer
-    #[cfg(feature = "optimizer")]
-    fn do_optimize(&self, source: String, kind: SourceKind) -> Result<String, String> {
-        if let Some(ref optimizer) = self.optimizer {
-            optimizer.optimize(source, kind)
-        } else {
-            Ok(source)
+    /// Helper function which inserts the default precision statements after the `#version` and
+    /// `#extension` directives of a processed fragment shader, if they are required.
+    fn insert_default_precision(&self,
+                                lang: Language,
+                                source: String,
+                                kind: SourceKind,
+                                source_map: &mut SourceMap)
+                                -> String {
+        let (float, int) = match self.default_precision {
+            Some(precisions) => precisions,
+            None => return source,
+        };
+        if kind != SourceKind::Fragment || !lang.is_gles() {
+            return source;
         }
+        let precision = format!("precision {} float;\nprecision {} int;\n",
+                                precision_keyword(float),
+                                precision_keyword(int));
+        insert_after_extensions(&source, &precision, source_map)
     }
 
-    #[cfg(not(feature = "optimizer"))]
-    fn do_optimize(&self, source: String) -> String {
-        // dummy
-        source
+    /// Helper function which emits a warning diagnostic from the build script, or to stderr if
+    /// the shaders are written to an explicit `out_dir()`.
+    fn warning(&self, message: &str) {
+        if self.out_dir.is_some() {
+            eprintln!("warning: {}", message);
+        } else {
+            println!("cargo:warning={}", message);
+        }
     }
 }
 
@@ -432,6 +974,19 @@ struct Source {
     kind: SourceKind,
 }
 
+/// A file to be processed, with the state inherited from the file which includes it.
+struct Inclusion<'a, 'b> {
+    name: &'a str,
+    source: &'b str,
+    file_id: usize,
+    /// The files which include this one, directly or indirectly
+    include_stack: Vec<&'b str>,
+    /// The version of the file which includes this one, if any
+    version: Option<Version>,
+    /// true if the file is included from within a conditional block
+    conditional: bool,
+}
+
 impl Source {
     pub fn new(path: PathBuf, kind: SourceKind) -> Source {
         Source {
@@ -441,10 +996,366 @@ impl Source {
     }
 }
 
+/// Helper function which assigns a file ID to every shader and include name.
+///
+/// IDs are derived from a hash of the name rather than the order files are encountered in, so
+/// they are stable across builds and unaffected by other files being added or removed. 0 is never
+/// used, as it is the source string number of shaders without `#line` directives. Collisions are
+/// resolved by taking the next free ID, in the order of the names.
+fn file_ids<'a, I: Iterator<Item = &'a str>>(names: I) -> HashMap<String, usize> {
+    const MAX_ID: u32 = 0x7fff_ffff;
+
+    let mut names: Vec<&str> = names.collect();
+    names.sort();
+    names.dedup();
+
+    let mut ids = HashMap::new();
+    let mut used = HashSet::new();
+    for name in names {
+        // 32-bit FNV-1a, limited to the range of a GLSL int
+        let hash = name.bytes()
+            .fold(0x811c_9dc5u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x0100_0193));
+        let mut id = hash & MAX_ID;
+        while id == 0 || !used.insert(id) {
+            id = (id + 1) & MAX_ID;
+        }
+        ids.insert(name.to_string(), id as usize);
+    }
+    ids
+}
+
+/// Helper function which returns the version assumed for shaders without a `#version` directive.
+///
+/// As in the GLSL ES specification, such shaders are GLSL ES 1.00 for every version of OpenGL ES.
+fn default_version(lang: Language) -> Version {
+    match lang {
+        Language::OpenGl => Version::new(110, None),
+        Language::OpenGl33Core => Version::new(330, Some(Profile::Core)),
+        Language::OpenGl4x => Version::new(400, Some(Profile::Core)),
+        Language::OpenGlEs20 |
+        Language::OpenGlEs30 |
+        Language::OpenGlEs31 |
+        Language::OpenGlEs32 |
+        Language::WebGl1 |
+        Language::WebGl2 => Version::new(100, None),
+    }
+}
+
+/// Helper function which determines if a GLSL version can be compiled by a target language.
+fn version_accepted(lang: Language, version: Version) -> bool {
+    const DESKTOP: &[u32] = &[110, 120, 130, 140, 150, 330, 400, 410, 420, 430, 440, 450, 460];
+    const ES: &[u32] = &[100, 300, 310, 320];
+
+    let (versions, min, max) = match lang {
+        Language::OpenGl => (DESKTOP, 110, 460),
+        Language::OpenGl33Core => (DESKTOP, 140, 330),
+        Language::OpenGl4x => (DESKTOP, 140, 460),
+        Language::OpenGlEs20 => (ES, 100, 100),
+        Language::OpenGlEs30 => (ES, 100, 300),
+        Language::OpenGlEs31 => (ES, 100, 310),
+        Language::OpenGlEs32 => (ES, 100, 320),
+        Language::WebGl1 => (ES, 100, 100),
+        Language::WebGl2 => (ES, 100, 300),
+    };
+    let profile_ok = match version.effective_profile() {
+        Some(Profile::Es) => lang.is_gles(),
+        // Core profile contexts cannot compile compatibility profile shaders
+        Some(Profile::Compatibility) => lang == Language::OpenGl,
+        _ => !lang.is_gles(),
+    };
+    profile_ok && versions.contains(&version.number) && version.number >= min &&
+    version.number <= max
+}
+
+/// Helper function which fails if the optimizer changed the precision of any variable that was
+/// declared with an explicit precision qualifier.
+fn check_precisions(lang: Language,
+                    name: &str,
+                    declared: &HashMap<String, Precision>,
+                    info: &ShaderInfo)
+                    -> Result<(), String> {
+    if !lang.is_gles() {
+        return Ok(());
+    }
+    let vars = info.inputs.iter().chain(info.uniforms.iter()).chain(info.textures.iter());
+    for var in vars {
+        if let Some(&precision) = declared.get(&var.name) {
+            if precision != var.precision {
+                return Err(format!("optimizer changed the precision of \"{}\" in shader source \
+                                    \"{}\" from {} to {}",
+                                   var.name,
+                                   name,
+                                   precision_keyword(precision),
+                                   precision_keyword(var.precision)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Helper function which compiles a processed shader source to SPIR-V with naga, and writes both
+/// the binary and a Rust expression containing it.
+#[cfg(feature = "spirv")]
+fn write_spirv(out_path: &Path,
+               name: &str,
+               source: &str,
+               kind: SourceKind,
+               source_map: &SourceMap)
+               -> Result<(), String> {
+    let words = cross::spirv(name, source, kind, source_map).map_err(|err| {
+            format!("SPIR-V compilation error for shader source \"{}\":\n{}", name, err)
+        })?;
+
+    let mut bytes = Vec::with_capacity(words.len() * 4);
+    for word in words.iter() {
+        bytes.extend_from_slice(&[*word as u8,
+                                  (*word >> 8) as u8,
+                                  (*word >> 16) as u8,
+                                  (*word >> 24) as u8]);
+    }
+    write_file(&out_path.join(format!("{}.spv", name)), bytes)?;
+
+    let mut rust = String::from("&[");
+    for (idx, word) in words.iter().enumerate() {
+        let separator = if idx % 8 == 0 { "\n    " } else { " " };
+        rust.push_str(&format!("{}{:#010x},", separator, word));
+    }
+    rust.push_str("\n]\n");
+    write_file(&out_path.join(format!("{}.spv.rs", name)), rust)
+}
+
+#[cfg(not(feature = "spirv"))]
+fn write_spirv(_out_path: &Path,
+               _name: &str,
+               _source: &str,
+               _kind: SourceKind,
+               _source_map: &SourceMap)
+               -> Result<(), String> {
+    // dummy
+    Ok(())
+}
+
+/// Helper function which translates a processed shader source to WGSL with naga, and writes it.
+#[cfg(feature = "wgsl")]
+fn write_wgsl(out_path: &Path,
+              name: &str,
+              source: &str,
+              kind: SourceKind,
+              source_map: &SourceMap)
+              -> Result<(), String> {
+    let wgsl = cross::wgsl(name, source, kind, source_map).map_err(|err| {
+            format!("WGSL translation error for shader source \"{}\":\n{}", name, err)
+        })?;
+
+    write_file(&out_path.join(format!("{}.wgsl", name)), wgsl)
+}
+
+#[cfg(not(feature = "wgsl"))]
+fn write_wgsl(_out_path: &Path,
+              _name: &str,
+              _source: &str,
+              _kind: SourceKind,
+              _source_map: &SourceMap)
+              -> Result<(), String> {
+    // dummy
+    Ok(())
+}
+
+/// Helper function which writes a compressed copy of a shader source.
+#[cfg(feature = "compress")]
+fn write_compressed(out_path: &Path, name: &str, source: &str) -> Result<(), String> {
+    write_file(&out_path.join(format!("{}.deflate", name)),
+               glossy::compress::compress(source))
+}
+
+#[cfg(not(feature = "compress"))]
+fn write_compressed(_out_path: &Path, _name: &str, _source: &str) -> Result<(), String> {
+    // dummy
+    Ok(())
+}
+
+/// Helper function which returns the GLSL keyword for a precision.
+fn precision_keyword(precision: Precision) -> &'static str {
+    match precision {
+        Precision::High => "highp",
+        Precision::Medium => "mediump",
+        Precision::Low => "lowp",
+    }
+}
+
+/// Helper function which collects the names of global variables declared with an explicit
+/// precision qualifier. Outputs are not collected, as the optimizer does not report them, so
+/// their precisions cannot be checked.
+fn declared_precisions(source: &str) -> HashMap<String, Precision> {
+    lazy_static! {
+        static ref DECL_RE: Regex = Regex::new(r"(?m)^\s*(?:uniform|attribute|varying|in)\s+(lowp|mediump|highp)\s+\w+\s+(\w+)").unwrap();
+    }
+
+    DECL_RE.captures_iter(source)
+        .map(|cap| {
+            let precision = match cap.at(1).unwrap() {
+                "highp" => Precision::High,
+                "mediump" => Precision::Medium,
+                _ => Precision::Low,
+            };
+            (cap.at(2).unwrap().to_string(), precision)
+        })
+        .collect()
+}
+
+/// Helper function which parses the tokens following `#version`.
+fn parse_version(tokens: &[Token]) -> Option<Version> {
+    match *tokens {
+        [Token::Number(number)] => Version::parse(number, None),
+        [Token::Number(number), Token::Ident(profile)] => Version::parse(number, Some(profile)),
+        _ => None,
+    }
+}
+
+/// Helper function which records an `#extension` directive, merging it with any previous
+/// directive for the same extension.
+fn add_extension(extensions: &mut Vec<(String, String)>,
+                 file: &str,
+                 name: &str,
+                 behavior: &str)
+                 -> Result<(), String> {
+    // Behaviors other than disable can be merged by keeping the strongest one
+    fn strength(behavior: &str) -> u32 {
+        match behavior {
+            "require" => 3,
+            "enable" => 2,
+            "warn" => 1,
+            _ => 0,
+        }
+    }
+
+    if !["require", "enable", "warn", "disable"].contains(&behavior) {
+        return Err(format!("shader file \"{}\" specifies invalid behavior \"{}\" for extension {}",
+                           file,
+                           behavior,
+                           name));
+    }
+    match extensions.iter_mut().find(|&&mut (ref n, _)| n == name) {
+        Some(&mut (_, ref mut existing)) => {
+            if (existing == "disable") != (behavior == "disable") {
+                return Err(format!("shader file \"{}\" specifies \"{}\" for extension {}, which \
+                                    conflicts with \"{}\" specified elsewhere",
+                                   file,
+                                   behavior,
+                                   name,
+                                   existing));
+            }
+            if strength(behavior) > strength(existing) {
+                *existing = behavior.to_string();
+            }
+        }
+        None => extensions.push((name.to_string(), behavior.to_string())),
+    }
+    Ok(())
+}
+
+/// Helper function which returns the length of the `#version` and `#extension` directives at the
+/// start of a processed shader source.
+fn preamble_len(source: &str) -> usize {
+    let mut len = 0;
+    for line in source.split('\n') {
+        let directive = line.trim_start();
+        if len > 0 && !directive.starts_with("#extension") {
+            break;
+        }
+        len += line.len() + 1;
+    }
+    cmp::min(len, source.len())
+}
+
+/// Helper function which inserts some lines after the `#version` and `#extension` directives of a
+/// processed shader source, and accounts for them in its source map. Conditional `#extension`
+/// directives are left in the body, so the lines may go after some of it, in which case they are
+/// followed by a `#line` directive which restores the numbering of the body.
+fn insert_after_extensions(source: &str, text: &str, source_map: &mut SourceMap) -> String {
+    if text.is_empty() {
+        return source.to_string();
+    }
+
+    // The number of physical lines before the inserted ones, and the last #line directive before
+    // them as its number, file and the number of physical lines up to it
+    let mut insert_at = 0;
+    let mut line_directive = None;
+    let mut last_line_directive = None;
+    // The depth of the conditional block the current line is in, and whether the outermost one
+    // contains an #extension directive
+    let mut conditional_depth = 0usize;
+    let mut conditional_extension = false;
+    for line in lexer::lines(source) {
+        let end = line.number - 1 + line.span;
+        let directive = match line.directive() {
+            Some(directive) => directive,
+            None if line.is_empty() => continue,
+            // Extensions must precede any other tokens
+            None => break,
+        };
+        match directive.name {
+            "version" => insert_at = end,
+            "extension" if conditional_depth == 0 => {
+                insert_at = end;
+                line_directive = last_line_directive.clone();
+            }
+            "extension" => conditional_extension = true,
+            "if" | "ifdef" | "ifndef" => conditional_depth += 1,
+            "endif" => {
+                conditional_depth = conditional_depth.saturating_sub(1);
+                if conditional_depth == 0 && conditional_extension {
+                    insert_at = end;
+                    line_directive = last_line_directive.clone();
+                    conditional_extension = false;
+                }
+            }
+            "line" => {
+                last_line_directive = match directive.tokens[..] {
+                    [Token::Number(number), Token::Number(file)] => {
+                        number.parse().ok().map(|number: usize| (number, file.to_string(), end))
+                    }
+                    [Token::Number(number), Token::Str(file)] => {
+                        number.parse()
+                            .ok()
+                            .map(|number: usize| (number, format!("\"{}\"", file), end))
+                    }
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let mut inserted = text.to_string();
+    if let Some((number, file, directive_end)) = line_directive {
+        // The line which follows the inserted ones keeps its number, whichever way the version
+        // numbers the lines after a #line directive
+        inserted.push_str(&format!("#line {} {}\n", number + insert_at - directive_end, file));
+    }
+    source_map.insert(insert_at, inserted.matches('\n').count());
+    let idx = match insert_at {
+        0 => 0,
+        _ => source.match_indices('\n').nth(insert_at - 1).map_or(source.len(), |(idx, _)| idx + 1),
+    };
+    format!("{}{}{}", &source[..idx], inserted, &source[idx..])
+}
+
 /// Helper function which reads the contents of a file as a string.
-fn read_file<P: AsRef<Path>>(p: P) -> String {
-    let mut file = File::open(p).unwrap();
+fn read_file<P: AsRef<Path>>(p: P) -> Result<String, String> {
+    let p = p.as_ref();
     let mut s = String::new();
-    file.read_to_string(&mut s).unwrap();
-    s
+    File::open(p)
+        .and_then(|mut file| file.read_to_string(&mut s))
+        .map_err(|err| format!("cannot read \"{}\": {}", p.display(), err))?;
+    Ok(s)
+}
+
+/// Helper function which writes the contents of a generated file.
+fn write_file<C: AsRef<[u8]>>(p: &Path, contents: C) -> Result<(), String> {
+    use std::io::Write;
+
+    File::create(p)
+        .and_then(|mut file| file.write_all(contents.as_ref()))
+        .map_err(|err| format!("cannot write \"{}\": {}", p.display(), err))
 }
@@ -0,0 +1,764 @@
+//! A pure-Rust optimizer which works on the tokens of a shader rather than a full GLSL parse.
+//!
+//! It folds constant arithmetic on literals, removes functions which cannot be reached from
+//! `main`, optionally renames the identifiers which are private to the shader, and removes all
+//! redundant parentheses and whitespace. It is much less thorough than glsl-optimizer, but has no
+//! native dependencies and accepts every GLSL version.
+//!
+//! Every item keeps the line of the input it came from, so that the lines of the output can be
+//! traced back to the original files. `#line` directives are removed, as the lines they number
+//! are joined.
+
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use Language;
+use lexer::{self, Token};
+use optimize::{Backend, Optimized, OptimizerOptions, SourceKind};
+use version::Version;
+
+/// Directives which do not affect the code around them, so the code can still be analyzed as a
+/// whole when they are present.
+const INERT_DIRECTIVES: &[&str] = &["version", "extension", "pragma", "line"];
+
+/// Assignment operators, which bind more loosely than any arithmetic operator.
+const ASSIGNMENTS: &[&str] = &["=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|="];
+
+/// Qualifiers of global variables which are visible outside the shader.
+const INTERFACE_QUALIFIERS: &[&str] =
+    &["uniform", "attribute", "varying", "in", "out", "buffer", "shared", "layout", "centroid",
+      "flat", "smooth", "noperspective", "invariant", "patch", "sample"];
+
+/// Qualifiers which may precede the type of a local variable or parameter.
+const QUALIFIERS: &[&str] = &["const", "in", "out", "inout", "highp", "mediump", "lowp",
+                             "precise"];
+
+/// Keywords which may start a statement that is not a declaration.
+const STATEMENT_KEYWORDS: &[&str] = &["return", "else", "case", "default", "break", "continue",
+                                     "discard", "do", "if", "while", "for", "switch", "struct"];
+
+/// Keywords, reserved words and built-in functions which could otherwise be chosen as new names.
+const RESERVED: &[&str] =
+    &["do", "if", "in", "abs", "all", "any", "asm", "cos", "dot", "exp", "fma", "for", "int",
+      "log", "max", "min", "mix", "mod", "not", "out", "pow", "sin", "tan", "acos", "asin",
+      "atan", "bool", "case", "cast", "ceil", "cosh", "else", "enum", "flat", "goto", "half",
+      "long", "lowp", "main", "modf", "sign", "sinh", "sqrt", "step", "tanh", "this", "true",
+      "uint", "void"];
+
+pub struct Minifier {
+    full_shader: bool,
+    rename_identifiers: bool,
+}
+
+impl Minifier {
+    pub fn new(options: OptimizerOptions) -> Minifier {
+        Minifier {
+            full_shader: options.full_shader,
+            rename_identifiers: options.rename_identifiers,
+        }
+    }
+}
+
+impl Backend for Minifier {
+    fn optimize(&self, source: String, _kind: SourceKind) -> Result<Optimized, String> {
+        let (mut items, mut lines) = items(&source);
+        let analyzable = items.iter().all(|item| match *item {
+            Item::Directive(ref name, _) => INERT_DIRECTIVES.contains(&name.as_str()),
+            _ => true,
+        });
+        // Identifiers may be function-like macros, whose arguments must be kept as they are
+        if analyzable {
+            fold_constants(&mut items, &mut lines);
+        }
+        if analyzable && self.full_shader {
+            let (live_items, live_lines) = remove_dead_functions(items, lines);
+            items = live_items;
+            lines = live_lines;
+        }
+        if analyzable && self.rename_identifiers {
+            rename_identifiers(&mut items);
+        }
+        remove_parentheses(&mut items, &mut lines, analyzable);
+        let (source, lines) = render(&items, &lines);
+        Ok(Optimized {
+            source: source,
+            info: None,
+            lines: Some(lines),
+        })
+    }
+
+    fn supports(&self, _lang: Language, _version: Version) -> bool {
+        true
+    }
+}
+
+/// A token of a shader, or a whole preprocessor directive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Item {
+    /// A directive with its name and the trimmed text of its line, which must stay on a line of
+    /// its own
+    Directive(String, String),
+    Ident(String),
+    Number(String),
+    Punct(String),
+}
+
+impl Item {
+    fn text(&self) -> &str {
+        match *self {
+            Item::Directive(_, ref text) |
+            Item::Ident(ref text) |
+            Item::Number(ref text) |
+            Item::Punct(ref text) => text,
+        }
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        *self == Item::Punct(punct.to_string())
+    }
+}
+
+/// Splits a shader source into items, without comments or `#line` directives, and returns them
+/// with the line each of them is on.
+pub fn items(source: &str) -> (Vec<Item>, Vec<usize>) {
+    let mut items = Vec::new();
+    let mut lines = Vec::new();
+    for line in lexer::lines(source) {
+        if let Some(directive) = line.directive() {
+            if directive.name != "line" {
+                items.push(Item::Directive(directive.name.to_string(),
+                                           line.code.trim().to_string()));
+                lines.push(line.number);
+            }
+            continue;
+        }
+        for token in lexer::tokenize(&line.code, false) {
+            items.push(match token {
+                Token::Ident(s) => Item::Ident(s.to_string()),
+                Token::Number(s) => Item::Number(s.to_string()),
+                Token::Str(s) => Item::Punct(format!("\"{}\"", s)),
+                Token::Header(s) | Token::Punct(s) => Item::Punct(s.to_string()),
+            });
+            lines.push(line.number);
+        }
+    }
+    (items, lines)
+}
+
+/// Joins items back into a shader source with as little whitespace as possible. Top-level
+/// declarations are put on lines of their own, to keep lines short. Returns the source with the
+/// line of the first item on each of its lines.
+pub fn render(items: &[Item], lines: &[usize]) -> (String, Vec<usize>) {
+    let mut output = String::new();
+    let mut output_lines = Vec::new();
+    let mut depth = 0;
+    let mut prev: Option<&Item> = None;
+    for (idx, item) in items.iter().enumerate() {
+        if let Item::Directive(_, ref text) = *item {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(text);
+            output.push('\n');
+            output_lines.push(lines[idx]);
+            prev = None;
+            continue;
+        }
+        if let Some(prev) = prev {
+            if needs_space(prev.text(), item.text()) {
+                output.push(' ');
+            }
+        } else {
+            output_lines.push(lines[idx]);
+        }
+        output.push_str(item.text());
+        prev = Some(item);
+
+        match item.text() {
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            _ => {}
+        }
+        let declaration_end = item.is_punct(";") ||
+                              (item.is_punct("}") &&
+                               !items.get(idx + 1).is_some_and(|next| next.is_punct(";")));
+        if depth == 0 && declaration_end {
+            output.push('\n');
+            prev = None;
+        }
+    }
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    (output, output_lines)
+}
+
+/// Returns true if two tokens would not be read back as the same tokens when written without
+/// whitespace between them.
+fn needs_space(prev: &str, next: &str) -> bool {
+    if prev.ends_with('/') && (next.starts_with('/') || next.starts_with('*')) {
+        return true;
+    }
+    let joined = format!("{}{}", prev, next);
+    match lexer::tokenize(&joined, false).first() {
+        Some(&Token::Ident(s)) | Some(&Token::Number(s)) | Some(&Token::Punct(s)) => s != prev,
+        _ => true,
+    }
+}
+
+/// Returns the precedence of a binary operator, higher binding more tightly, or `None` if the
+/// item is not a binary operator.
+fn precedence(item: &Item) -> Option<u32> {
+    match *item {
+        Item::Punct(ref op) => {
+            match op.as_str() {
+                "*" | "/" | "%" => Some(10),
+                "+" | "-" => Some(9),
+                "<<" | ">>" => Some(8),
+                "<" | ">" | "<=" | ">=" => Some(7),
+                "==" | "!=" => Some(6),
+                "&" => Some(5),
+                "^" => Some(4),
+                "|" => Some(3),
+                "&&" => Some(2),
+                "^^" => Some(1),
+                "||" => Some(0),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the precedence with which an item binds the operand which follows it, or `None` if
+/// the operand could be bound more tightly, e.g. by a unary operator or a function call.
+fn left_binding(item: &Item) -> Option<u32> {
+    match *item {
+        Item::Punct(ref op) if ["(", "[", ",", ";", "{", "}", "?", ":"].contains(&op.as_str()) ||
+                               ASSIGNMENTS.contains(&op.as_str()) => Some(0),
+        Item::Ident(ref ident) if ident == "return" => Some(0),
+        _ => precedence(item).map(|precedence| precedence + 1),
+    }
+}
+
+/// Returns the precedence with which an item binds the operand which precedes it, or `None` if
+/// the operand could be bound more tightly, e.g. by a swizzle or an index.
+fn right_binding(item: &Item) -> Option<u32> {
+    match *item {
+        Item::Punct(ref op) if [")", "]", ",", ";", "}", "?", ":"].contains(&op.as_str()) => {
+            Some(0)
+        }
+        _ => precedence(item),
+    }
+}
+
+/// A literal value, parsed from a number token without a suffix.
+#[derive(Copy, Clone)]
+enum Literal {
+    Int(i32),
+    Float(f32),
+}
+
+impl Literal {
+    fn parse(item: &Item) -> Option<Literal> {
+        let text = match *item {
+            Item::Number(ref text) => text,
+            _ => return None,
+        };
+        let decimal = text.chars().all(|c| c.is_ascii_digit());
+        if decimal && (text == "0" || !text.starts_with('0')) {
+            text.parse().ok().map(Literal::Int)
+        } else if text.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) && !decimal {
+            text.parse().ok().map(Literal::Float)
+        } else {
+            None
+        }
+    }
+
+    fn apply(self, op: &str, rhs: Literal) -> Option<Literal> {
+        match (self, rhs) {
+            (Literal::Int(a), Literal::Int(b)) => {
+                match op {
+                    "+" => a.checked_add(b),
+                    "-" => a.checked_sub(b),
+                    "*" => a.checked_mul(b),
+                    "/" if b != 0 => a.checked_div(b),
+                    _ => None,
+                }
+                .map(Literal::Int)
+            }
+            (Literal::Float(a), Literal::Float(b)) => {
+                let value = match op {
+                    "+" => a + b,
+                    "-" => a - b,
+                    "*" => a * b,
+                    "/" => a / b,
+                    _ => return None,
+                };
+                if value.is_finite() { Some(Literal::Float(value)) } else { None }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the items which make up the literal, with a separate minus sign if negative.
+    fn items(self) -> Vec<Item> {
+        let (negative, text) = match self {
+            Literal::Int(value) => (value < 0, value.abs().to_string()),
+            Literal::Float(value) => (value < 0.0, format!("{:?}", value.abs())),
+        };
+        let number = Item::Number(text);
+        if negative {
+            vec![Item::Punct("-".to_string()), number]
+        } else {
+            vec![number]
+        }
+    }
+}
+
+/// Folds arithmetic on pairs of literals, such as `2.0 * 0.5`, wherever the surrounding operators
+/// bind the literals less tightly than the operator between them.
+fn fold_constants(items: &mut Vec<Item>, lines: &mut Vec<usize>) {
+    let mut idx = 1;
+    while idx + 2 < items.len() {
+        let folded = match (Literal::parse(&items[idx]), Literal::parse(&items[idx + 2])) {
+            (Some(lhs), Some(rhs)) => {
+                let op = &items[idx + 1];
+                let bound = precedence(op).is_some_and(|precedence| {
+                    left_binding(&items[idx - 1]).is_some_and(|left| left <= precedence) &&
+                    items.get(idx + 3)
+                        .is_some_and(|next| {
+                            right_binding(next).is_some_and(|right| right <= precedence)
+                        })
+                });
+                if bound { lhs.apply(op.text(), rhs) } else { None }
+            }
+            _ => None,
+        };
+        match folded {
+            Some(literal) => {
+                let literal = literal.items();
+                let line = lines[idx];
+                lines.splice(idx..idx + 3, literal.iter().map(|_| line));
+                items.splice(idx..idx + 3, literal);
+                // The result may now fold with the operand before it
+                idx = cmp::max(idx, 3) - 2;
+            }
+            None => idx += 1,
+        }
+    }
+}
+
+/// A top-level declaration of a shader.
+struct Declaration {
+    start: usize,
+    end: usize,
+    /// The name of the function, if the declaration is a function definition or prototype
+    function: Option<String>,
+    /// True if the declaration is a function definition
+    body: bool,
+}
+
+/// Splits items into top-level declarations.
+fn declarations(items: &[Item]) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut body = false;
+    for (idx, item) in items.iter().enumerate() {
+        let end = match *item {
+            Item::Directive(..) => true,
+            Item::Punct(ref p) if p == "(" || p == "[" => {
+                depth += 1;
+                false
+            }
+            Item::Punct(ref p) if p == ")" || p == "]" => {
+                depth -= 1;
+                false
+            }
+            Item::Punct(ref p) if p == "{" => {
+                if depth == 0 && idx > 0 && items[idx - 1].is_punct(")") {
+                    body = true;
+                }
+                depth += 1;
+                false
+            }
+            Item::Punct(ref p) if p == "}" => {
+                depth -= 1;
+                depth == 0 && body
+            }
+            Item::Punct(ref p) if p == ";" => depth == 0,
+            _ => false,
+        };
+        if end {
+            declarations.push(Declaration {
+                start: start,
+                end: idx + 1,
+                function: function_name(&items[start..idx + 1]),
+                body: body,
+            });
+            start = idx + 1;
+            body = false;
+        }
+    }
+    if start < items.len() {
+        declarations.push(Declaration {
+            start: start,
+            end: items.len(),
+            function: None,
+            body: false,
+        });
+    }
+    declarations
+}
+
+/// Returns the name of the function a declaration defines or declares, if any.
+fn function_name(items: &[Item]) -> Option<String> {
+    let paren = items.iter().position(|item| item.is_punct("("))?;
+    if paren < 2 || items[..paren].iter().any(|item| item.is_punct("=")) {
+        return None;
+    }
+    match items[paren - 1] {
+        Item::Ident(ref name) if name != "layout" => {
+            let after = &items[paren..];
+            let last = after.last()?;
+            if last.is_punct("}") || (last.is_punct(";") && !after.iter().any(|i| i.is_punct("=")))
+            {
+                Some(name.clone())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Removes the definitions and prototypes of functions which are never called, directly or
+/// indirectly, from `main` or from any other declaration. Sources without a `main` function are
+/// left unchanged, as they may be linked with other shaders.
+fn remove_dead_functions(items: Vec<Item>, lines: Vec<usize>) -> (Vec<Item>, Vec<usize>) {
+    let declarations = declarations(&items);
+    if !declarations.iter().any(|d| d.body && d.function.as_ref().is_some_and(|f| f == "main")) {
+        return (items, lines);
+    }
+
+    // The identifiers used by each function, and by everything else
+    let mut uses: HashMap<&str, HashSet<&str>> = HashMap::new();
+    let mut roots = vec!["main"];
+    for declaration in declarations.iter() {
+        let idents = items[declaration.start..declaration.end].iter().filter_map(|item| {
+            match *item {
+                Item::Ident(ref ident) => Some(ident.as_str()),
+                _ => None,
+            }
+        });
+        match declaration.function {
+            Some(ref name) => uses.entry(name).or_default().extend(idents),
+            None => roots.extend(idents),
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    while let Some(name) = roots.pop() {
+        if reachable.insert(name) {
+            if let Some(used) = uses.get(name) {
+                roots.extend(used.iter().cloned());
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(items.len());
+    let mut output_lines = Vec::with_capacity(lines.len());
+    for declaration in declarations.iter() {
+        let live = declaration.function.as_ref().is_none_or(|f| reachable.contains(f.as_str()));
+        if live {
+            output.extend(items[declaration.start..declaration.end].iter().cloned());
+            output_lines.extend(lines[declaration.start..declaration.end].iter().cloned());
+        }
+    }
+    (output, output_lines)
+}
+
+/// Removes parentheses around single operands, and around expressions which are delimited by
+/// tokens that bind less tightly than any operator, such as `x = (a + b);`. Unless the source is
+/// `analyzable`, identifiers may be macros which expand to whole expressions, and parentheses may
+/// be part of the arguments of function-like macros, so only those around single numbers are
+/// removed.
+fn remove_parentheses(items: &mut Vec<Item>, lines: &mut Vec<usize>, analyzable: bool) {
+    let mut idx = 1;
+    while idx < items.len() {
+        if !items[idx].is_punct("(") {
+            idx += 1;
+            continue;
+        }
+        let close = match matching(items, idx) {
+            Some(close) => close,
+            None => break,
+        };
+        let (prev, next) = match items.get(close + 1) {
+            Some(next) => (&items[idx - 1], next),
+            None => break,
+        };
+        let inner = &items[idx + 1..close];
+        let operand = match *prev {
+            Item::Punct(ref p) => p != ")" && p != "]",
+            Item::Ident(ref ident) => ident == "return",
+            _ => false,
+        };
+        let removable = if inner.len() == 1 {
+            let number_member = match (&inner[0], next) {
+                (&Item::Number(_), &Item::Punct(ref p)) => p == ".",
+                _ => false,
+            };
+            let macro_use = match inner[0] {
+                Item::Ident(_) => !analyzable,
+                _ => false,
+            };
+            operand && !number_member && !macro_use
+        } else {
+            // Commas at the top level of the parentheses would become argument separators
+            let mut depth = 0;
+            let comma = inner.iter().any(|item| {
+                match item.text() {
+                    "(" | "[" => depth += 1,
+                    ")" | "]" => depth -= 1,
+                    "," => return depth == 0,
+                    _ => {}
+                }
+                false
+            });
+            let delimited = left_binding(prev) == Some(0) && !prev.is_punct(":") &&
+                            !prev.is_punct("?") &&
+                            [")", "]", ";", ","].contains(&next.text());
+            analyzable && operand && delimited && !comma && !inner.is_empty()
+        };
+        if removable {
+            items.remove(close);
+            items.remove(idx);
+            lines.remove(close);
+            lines.remove(idx);
+        } else {
+            idx += 1;
+        }
+    }
+}
+
+/// Returns the index of the parenthesis which closes the one at `open`.
+fn matching(items: &[Item], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, item) in items.iter().enumerate().skip(open) {
+        match item.text() {
+            "(" => depth += 1,
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Renames the functions and variables which are private to the shader to short names, the most
+/// used first. Interface variables, struct and block members and built-ins keep their names, and
+/// a name is only changed if it is never declared as one of them.
+fn rename_identifiers(items: &mut [Item]) {
+    let declarations = declarations(items);
+    let functions: HashSet<&str> =
+        declarations.iter().filter_map(|d| d.function.as_deref()).collect();
+    let mut declared = HashSet::new();
+    let mut preserved = HashSet::new();
+    for declaration in declarations.iter() {
+        let tokens = &items[declaration.start..declaration.end];
+        match declaration.function {
+            Some(ref name) => {
+                declared.insert(name.clone());
+                let open = tokens.iter().position(|item| item.is_punct("(")).unwrap();
+                let close = matching(tokens, open).unwrap_or(tokens.len());
+                for parameter in tokens[open + 1..close].split(|item| item.is_punct(",")) {
+                    let end = parameter.iter()
+                        .position(|item| item.is_punct("["))
+                        .unwrap_or(parameter.len());
+                    let idents: Vec<&str> = parameter[..end]
+                        .iter()
+                        .filter_map(|item| match *item {
+                            Item::Ident(ref ident) if !QUALIFIERS.contains(&ident.as_str()) => {
+                                Some(ident.as_str())
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    if idents.len() >= 2 {
+                        declared.insert(idents[idents.len() - 1].to_string());
+                    }
+                }
+                if declaration.body {
+                    local_declarations(&tokens[close..], &mut declared, &mut preserved);
+                }
+            }
+            None => {
+                let interface = tokens.iter().any(|item| match *item {
+                    Item::Ident(ref ident) => {
+                        INTERFACE_QUALIFIERS.contains(&ident.as_str()) || ident == "struct" ||
+                        ident == "precision"
+                    }
+                    Item::Directive(..) => true,
+                    _ => false,
+                });
+                if interface {
+                    preserved.extend(identifiers(tokens).map(|ident| ident.to_string()));
+                } else {
+                    variable_names(tokens, &mut declared);
+                }
+            }
+        }
+    }
+
+    // Names which are called without being declared as functions are built-in functions, which
+    // the shader may shadow with variables of the same name
+    for idx in 1..items.len() {
+        if items[idx].is_punct("(") {
+            if let Item::Ident(ref ident) = items[idx - 1] {
+                if !functions.contains(ident.as_str()) {
+                    preserved.insert(ident.clone());
+                }
+            }
+        }
+    }
+
+    // Count the uses of each name to give the shortest names to the most used
+    let mut uses: HashMap<String, usize> = HashMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        if let Item::Ident(ref ident) = *item {
+            let member = idx > 0 && items[idx - 1].is_punct(".");
+            if !member && declared.contains(ident) && !preserved.contains(ident) &&
+               ident != "main" && !ident.starts_with("gl_") {
+                *uses.entry(ident.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut names: Vec<(String, usize)> = uses.into_iter().collect();
+    names.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let existing: HashSet<String> = identifiers(items).map(|ident| ident.to_string()).collect();
+    let mut renames = HashMap::new();
+    let mut counter = 0;
+    for (name, _) in names {
+        let new_name = loop {
+            let candidate = short_name(counter);
+            counter += 1;
+            if !existing.contains(&candidate) && !RESERVED.contains(&candidate.as_str()) {
+                break candidate;
+            }
+        };
+        if new_name.len() < name.len() {
+            renames.insert(name, new_name);
+        }
+    }
+
+    for idx in 0..items.len() {
+        let member = idx > 0 && items[idx - 1].is_punct(".");
+        let renamed = match items[idx] {
+            Item::Ident(ref ident) if !member => renames.get(ident).cloned(),
+            _ => None,
+        };
+        if let Some(name) = renamed {
+            items[idx] = Item::Ident(name);
+        }
+    }
+}
+
+/// Gathers the names of the local variables declared in a function body, and the identifiers in
+/// local struct declarations.
+fn local_declarations(items: &[Item],
+                      declared: &mut HashSet<String>,
+                      preserved: &mut HashSet<String>) {
+    for idx in 1..items.len() {
+        if let Item::Ident(ref ident) = items[idx] {
+            if ident == "struct" {
+                let end = items[idx..].iter().position(|item| item.is_punct("}"));
+                let end = end.map_or(items.len(), |end| idx + end);
+                preserved.extend(identifiers(&items[idx..end]).map(|ident| ident.to_string()));
+            }
+        }
+        let start = match items[idx - 1].text() {
+            "{" | "}" | ";" | ":" => true,
+            "(" => idx >= 2 && items[idx - 2] == Item::Ident("for".to_string()),
+            _ => false,
+        };
+        if start {
+            variable_names(&items[idx..], declared);
+        }
+    }
+}
+
+/// Gathers the names declared by a variable declaration at the start of `items`, such as
+/// `const float a = 1.0, b[2];`.
+fn variable_names(items: &[Item], declared: &mut HashSet<String>) {
+    let mut idx = 0;
+    while idx < items.len() {
+        match items[idx] {
+            Item::Ident(ref ident) if QUALIFIERS.contains(&ident.as_str()) => idx += 1,
+            _ => break,
+        }
+    }
+    match items.get(idx) {
+        Some(&Item::Ident(ref ty)) if !STATEMENT_KEYWORDS.contains(&ty.as_str()) => {}
+        _ => return,
+    }
+
+    // The type is followed by declarators separated by commas
+    let mut depth = 0;
+    let mut declarator = true;
+    for (offset, item) in items[idx + 1..].iter().enumerate() {
+        if declarator {
+            let following = items.get(idx + offset + 2).map(|item| item.text());
+            match (item, following) {
+                (&Item::Ident(ref name), Some(following))
+                    if ["=", ";", ",", "["].contains(&following) => {
+                    declared.insert(name.clone());
+                }
+                _ => return,
+            }
+            declarator = false;
+            continue;
+        }
+        match item.text() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => {
+                depth -= 1;
+                if depth < 0 {
+                    return;
+                }
+            }
+            "," if depth == 0 => declarator = true,
+            ";" if depth == 0 => return,
+            _ => {}
+        }
+    }
+}
+
+/// Returns the identifiers in a list of items.
+fn identifiers<'a>(items: &'a [Item]) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+    Box::new(items.iter().filter_map(|item| match *item {
+        Item::Ident(ref ident) => Some(ident.as_str()),
+        _ => None,
+    }))
+}
+
+/// Returns the short name with the given index: `a` to `Z`, then `aa`, `ab` and so on.
+fn short_name(mut idx: usize) -> String {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut name = Vec::new();
+    loop {
+        name.push(LETTERS[idx % LETTERS.len()]);
+        idx /= LETTERS.len();
+        if idx == 0 {
+            break;
+        }
+        idx -= 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
@@ -1,15 +1,218 @@
+#[cfg(feature = "minifier")]
+mod minifier;
 #[cfg(feature = "optimizer")]
 mod optimizer;
 
+#[cfg(feature = "minifier")]
+pub use self::minifier::*;
 #[cfg(feature = "optimizer")]
 pub use self::optimizer::*;
 
-#[cfg(not(feature = "optimizer"))]
-pub type Optimizer = ();
+use glossy::reflect::{BasicType, Precision};
+use Language;
+use version::Version;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+/// The kind of a shader source.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum SourceKind {
     Vertex,
     Fragment,
+    /// Any other kind of shader, such as a geometry or compute shader
     Unknown,
 }
+
+/// A shader source returned by an optimizer backend.
+pub struct Optimized {
+    pub source: String,
+    /// The inputs, uniforms and textures the shader uses, if the backend supports reflection
+    pub info: Option<ShaderInfo>,
+    /// The line of the input, numbered from 1, which each line of the source came from, if the
+    /// backend keeps track of it. Backends which move lines must also remove any `#line`
+    /// directives, as those would no longer number the lines correctly. Backends which keep
+    /// every line in place return the lines in order.
+    pub lines: Option<Vec<usize>>,
+}
+
+/// A backend which optimizes processed shader sources.
+pub trait Backend {
+    /// Optimizes a shader source.
+    fn optimize(&self, source: String, kind: SourceKind) -> Result<Optimized, String>;
+
+    /// Returns true if the backend is known to work on a version of a language. Other versions
+    /// are only optimized with `Config::allow_untested_versions`.
+    fn supports(&self, lang: Language, version: Version) -> bool;
+}
+
+/// The available optimizer backends.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OptimizerBackend {
+    /// glsl-optimizer, which is thorough and provides reflection info, but is written in C++ and
+    /// only tested with GLSL 1.10, 1.20, 1.00 ES and 3.00 ES. Its output cannot be traced back to
+    /// the original files, so the source maps of the shaders it optimizes are empty. This is only
+    /// available when the 'optimizer' feature is enabled.
+    #[cfg(feature = "optimizer")]
+    GlslOptimizer,
+    /// A minifier written in Rust, which folds constant arithmetic, removes functions which are
+    /// never called, redundant parentheses and whitespace, and can rename identifiers (see
+    /// `OptimizerOptions::rename_identifiers`). It works with every version of GLSL, but provides
+    /// no reflection info. As it joins lines, it removes `#line` directives, and the source map
+    /// is used to find the original lines instead. This is only available when the 'minifier'
+    /// feature is enabled.
+    #[cfg(feature = "minifier")]
+    Minifier,
+}
+
+/// Creates an optimizer backend for a language.
+#[allow(unused_variables)]
+pub fn new_backend(backend: OptimizerBackend,
+                   lang: Language,
+                   options: OptimizerOptions)
+                   -> Box<dyn Backend> {
+    match backend {
+        #[cfg(feature = "optimizer")]
+        OptimizerBackend::GlslOptimizer => Box::new(Optimizer::new(lang, options)),
+        #[cfg(feature = "minifier")]
+        OptimizerBackend::Minifier => Box::new(Minifier::new(options)),
+    }
+}
+
+/// Options which control how the optimizer backends process shaders. Options which only apply to
+/// one backend are ignored by the others.
+///
+/// The defaults match glossy's historical behavior: glsl-optimizer's own preprocessor is skipped,
+/// since glossy has already resolved `#include` directives, and every shader is treated as a
+/// complete shader.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct OptimizerOptions {
+    preprocess: bool,
+    full_shader: bool,
+    max_unroll_iterations: Option<u32>,
+    rename_identifiers: bool,
+}
+
+// The options can only be set when a backend is available
+#[cfg_attr(not(any(feature = "optimizer", feature = "minifier")), allow(dead_code))]
+impl OptimizerOptions {
+    /// Create the default set of optimizer options.
+    pub fn new() -> OptimizerOptions {
+        OptimizerOptions {
+            preprocess: false,
+            full_shader: true,
+            max_unroll_iterations: None,
+            rename_identifiers: false,
+        }
+    }
+
+    /// Let glsl-optimizer run its own preprocessor over the source, so that `#define` macros and
+    /// `#if` blocks are expanded before optimization.
+    pub fn preprocess(mut self, preprocess: bool) -> OptimizerOptions {
+        self.preprocess = preprocess;
+        self
+    }
+
+    /// Tell the optimizer that the sources are not complete shaders (for example, snippets which
+    /// will be concatenated with other code at runtime). This makes some optimizations weaker, as
+    /// unused declarations can no longer be removed.
+    pub fn full_shader(mut self, full_shader: bool) -> OptimizerOptions {
+        self.full_shader = full_shader;
+        self
+    }
+
+    /// Set the maximum number of iterations of a loop which glsl-optimizer will unroll.
+    pub fn max_unroll_iterations(mut self, iterations: u32) -> OptimizerOptions {
+        self.max_unroll_iterations = Some(iterations);
+        self
+    }
+
+    /// Let the minifier rename functions, function parameters, local variables and global
+    /// variables which are not visible outside the shader to short names. The names of uniforms,
+    /// inputs, outputs, interface blocks and structs are kept, so that they can still be bound by
+    /// name and matched between shaders.
+    pub fn rename_identifiers(mut self, rename: bool) -> OptimizerOptions {
+        self.rename_identifiers = rename;
+        self
+    }
+}
+
+impl Default for OptimizerOptions {
+    fn default() -> OptimizerOptions {
+        OptimizerOptions::new()
+    }
+}
+
+/// A variable reported by the optimizer's reflection API.
+#[derive(Clone, Debug)]
+pub struct Variable {
+    pub name: String,
+    pub ty: BasicType,
+    pub precision: Precision,
+    pub vec_size: u32,
+    pub mat_size: u32,
+    pub array_size: Option<u32>,
+    pub location: Option<u32>,
+}
+
+/// The inputs, uniforms and textures of an optimized shader.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderInfo {
+    pub inputs: Vec<Variable>,
+    pub uniforms: Vec<Variable>,
+    pub textures: Vec<Variable>,
+    pub uniform_total_size: u32,
+}
+
+impl ShaderInfo {
+    /// Converts the info to the form stored in shader archives.
+    pub fn to_archive(&self) -> glossy::archive::ShaderInfo {
+        let variables = |vars: &Vec<Variable>| {
+            vars.iter()
+                .map(|var| {
+                    glossy::archive::Variable {
+                        name: var.name.clone(),
+                        ty: var.ty,
+                        precision: var.precision,
+                        vec_size: var.vec_size,
+                        mat_size: var.mat_size,
+                        array_size: var.array_size,
+                        location: var.location,
+                    }
+                })
+                .collect()
+        };
+        glossy::archive::ShaderInfo {
+            inputs: variables(&self.inputs),
+            uniforms: variables(&self.uniforms),
+            textures: variables(&self.textures),
+            uniform_total_size: self.uniform_total_size,
+        }
+    }
+
+    /// Writes the info as a Rust expression which evaluates to a `&'static
+    /// glossy::reflect::ShaderInfo`.
+    pub fn write_rust<W: ::std::io::Write>(&self, w: &mut W) -> ::std::io::Result<()> {
+        write!(w, "&::glossy::reflect::ShaderInfo {{\n")?;
+        for &(field, vars) in &[("inputs", &self.inputs),
+                                ("uniforms", &self.uniforms),
+                                ("textures", &self.textures)] {
+            write!(w, "    {}: &[\n", field)?;
+            for var in vars.iter() {
+                write!(w,
+                       "        ::glossy::reflect::Variable {{ name: {:?}, \
+                        ty: ::glossy::reflect::BasicType::{:?}, \
+                        precision: ::glossy::reflect::Precision::{:?}, vec_size: {}, \
+                        mat_size: {}, array_size: {:?}, location: {:?} }},\n",
+                       var.name,
+                       var.ty,
+                       var.precision,
+                       var.vec_size,
+                       var.mat_size,
+                       var.array_size,
+                       var.location)?;
+            }
+            write!(w, "    ],\n")?;
+        }
+        write!(w,
+               "    uniform_total_size: {},\n}}\n",
+               self.uniform_total_size)
+    }
+}
@@ -2,28 +2,75 @@ extern crate libc;
 extern crate glsl_optimizer_sys as ffi;
 
 use std::ffi::{CStr, CString};
+use std::ptr;
+use glossy::reflect::{BasicType, Precision};
 use Language;
-use optimize::SourceKind;
+use optimize::{Backend, Optimized, OptimizerOptions, ShaderInfo, SourceKind, Variable};
+use version::{Profile, Version};
 
 pub struct Optimizer {
     ctx: *mut ffi::glslopt_ctx,
+    flags: ffi::glslopt_options,
 }
 
 impl Optimizer {
-    pub fn new(lang: Language) -> Optimizer {
+    pub fn new(lang: Language, options: OptimizerOptions) -> Optimizer {
         let lang = match lang {
-            Language::OpenGl => ffi::kGlslTargetOpenGL,
-            Language::OpenGlEs20 => ffi::kGlslTargetOpenGLES20,
-            Language::OpenGlEs30 => ffi::kGlslTargetOpenGLES30,
+            Language::OpenGl |
+            Language::OpenGl33Core |
+            Language::OpenGl4x => ffi::kGlslTargetOpenGL,
+            Language::OpenGlEs20 |
+            Language::WebGl1 => ffi::kGlslTargetOpenGLES20,
+            Language::OpenGlEs30 |
+            Language::WebGl2 |
+            Language::OpenGlEs31 |
+            Language::OpenGlEs32 => ffi::kGlslTargetOpenGLES30,
         };
-        Optimizer { ctx: unsafe { ffi::glslopt_initialize(lang) } }
+        let ctx = unsafe { ffi::glslopt_initialize(lang) };
+        if let Some(iterations) = options.max_unroll_iterations {
+            unsafe { ffi::glslopt_set_max_unroll_iterations(ctx, iterations) };
+        }
+        let mut flags = 0;
+        if !options.preprocess {
+            flags |= ffi::kGlslOptionSkipPreprocessor;
+        }
+        if !options.full_shader {
+            flags |= ffi::kGlslOptionNotFullShader;
+        }
+        Optimizer {
+            ctx: ctx,
+            flags: flags,
+        }
     }
+}
 
-    pub fn optimize(&self, source: String, kind: SourceKind) -> Result<String, String> {
+impl Backend for Optimizer {
+    fn optimize(&self, source: String, kind: SourceKind) -> Result<Optimized, String> {
         if kind == SourceKind::Unknown {
-            return Ok(source);
+            let lines = (1..source.lines().count() + 1).collect();
+            return Ok(Optimized {
+                source: source,
+                info: None,
+                lines: Some(lines),
+            });
+        }
+        // glsl-optimizer generates its output from its IR, so the lines of the output cannot be
+        // traced back to the input
+        let shader = Shader::new(self, source, kind);
+        Ok(Optimized {
+            source: shader.source()?,
+            info: Some(shader.info()),
+            lines: None,
+        })
+    }
+
+    fn supports(&self, lang: Language, version: Version) -> bool {
+        // According to the glsl-optimizer readme, versions of GLSL beyond 1.20 are untested
+        if lang.is_gles() {
+            version.number == 100 || version == Version::new(300, Some(Profile::Es))
+        } else {
+            !version.is_es() && (version.number == 110 || version.number == 120)
         }
-        Shader::new(self, source, kind).source()
     }
 }
 
@@ -49,7 +96,7 @@ impl Shader {
             ffi::glslopt_optimize(opt.ctx,
                                   kind,
                                   source.as_ptr(),
-                                  ffi::kGlslOptionSkipPreprocessor)
+                                  opt.flags)
         };
         Shader { shader: shader }
     }
@@ -67,6 +114,52 @@ impl Shader {
             }
         }
     }
+
+    fn info(&self) -> ShaderInfo {
+        unsafe {
+            ShaderInfo {
+                inputs: self.variables(ffi::glslopt_shader_get_input_count,
+                                       ffi::glslopt_shader_get_input_desc),
+                uniforms: self.variables(ffi::glslopt_shader_get_uniform_count,
+                                         ffi::glslopt_shader_get_uniform_desc),
+                textures: self.variables(ffi::glslopt_shader_get_texture_count,
+                                         ffi::glslopt_shader_get_texture_desc),
+                uniform_total_size: ffi::glslopt_shader_get_uniform_total_size(self.shader) as u32,
+            }
+        }
+    }
+
+    unsafe fn variables(&self, count: CountFn, desc: DescFn) -> Vec<Variable> {
+        (0..count(self.shader))
+            .map(|index| {
+                let mut name: *mut libc::c_char = ptr::null_mut();
+                let mut ty = 0;
+                let mut prec = 0;
+                let mut vec_size = 0;
+                let mut mat_size = 0;
+                let mut array_size = 0;
+                let mut location = 0;
+                desc(self.shader,
+                     index,
+                     &mut name,
+                     &mut ty,
+                     &mut prec,
+                     &mut vec_size,
+                     &mut mat_size,
+                     &mut array_size,
+                     &mut location);
+                Variable {
+                    name: CStr::from_ptr(name).to_string_lossy().into_owned(),
+                    ty: basic_type(ty),
+                    precision: precision(prec),
+                    vec_size: vec_size as u32,
+                    mat_size: mat_size as u32,
+                    array_size: if array_size >= 0 { Some(array_size as u32) } else { None },
+                    location: if location >= 0 { Some(location as u32) } else { None },
+                }
+            })
+            .collect()
+    }
 }
 
 impl Drop for Shader {
@@ -74,3 +167,36 @@ impl Drop for Shader {
         unsafe { ffi::glslopt_shader_delete(self.shader) };
     }
 }
+
+type CountFn = unsafe extern "C" fn(*mut ffi::glslopt_shader) -> libc::c_int;
+type DescFn = unsafe extern "C" fn(*mut ffi::glslopt_shader,
+                                   libc::c_int,
+                                   *const *mut libc::c_char,
+                                   *mut ffi::glslopt_basic_type,
+                                   *mut ffi::glslopt_precision,
+                                   *mut libc::c_int,
+                                   *mut libc::c_int,
+                                   *mut libc::c_int,
+                                   *mut libc::c_int);
+
+fn basic_type(ty: ffi::glslopt_basic_type) -> BasicType {
+    match ty {
+        ffi::kGlslTypeFloat => BasicType::Float,
+        ffi::kGlslTypeInt => BasicType::Int,
+        ffi::kGlslTypeBool => BasicType::Bool,
+        ffi::kGlslTypeTex2D => BasicType::Texture2D,
+        ffi::kGlslTypeTex3D => BasicType::Texture3D,
+        ffi::kGlslTypeTexCube => BasicType::TextureCube,
+        ffi::kGlslTypeTex2DShadow => BasicType::Texture2DShadow,
+        ffi::kGlslTypeTex2DArray => BasicType::Texture2DArray,
+        _ => BasicType::Other,
+    }
+}
+
+fn precision(prec: ffi::glslopt_precision) -> Precision {
+    match prec {
+        ffi::kGlslPrecMedium => Precision::Medium,
+        ffi::kGlslPrecLow => Precision::Low,
+        _ => Precision::High,
+    }
+}
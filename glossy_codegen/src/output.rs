@@ -0,0 +1,198 @@
+//! Processed shader sources which keep track of the original file and line of every line.
+//!
+//! `#line` directives are generated from this mapping when the source is rendered, rather than
+//! written as the source is processed, so that every line of the output can be traced back to
+//! the line it came from.
+
+use std::cmp;
+use std::io::{self, Write};
+use LineDirectives;
+use version::Version;
+
+/// The original location of a line of processed output.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Origin<'a> {
+    /// The id of the file, as listed in the generated `glossy::files::FileMap`
+    pub file_id: usize,
+    /// The name of the file
+    pub file: &'a str,
+    /// The line number in the file, starting from 1
+    pub line: usize,
+}
+
+/// A processed shader source, as a list of lines tagged with their origin.
+#[derive(Default)]
+pub struct Output<'a> {
+    lines: Vec<(String, Origin<'a>)>,
+    /// The files included directly or indirectly, in the order they were first included
+    includes: Vec<&'a str>,
+}
+
+impl<'a> Output<'a> {
+    pub fn new() -> Output<'a> {
+        Output {
+            lines: Vec::new(),
+            includes: Vec::new(),
+        }
+    }
+
+    /// Appends some text, which may span several lines. The first line originates from `origin`,
+    /// and each subsequent line from the following line of the same file.
+    pub fn push(&mut self, text: &str, origin: Origin<'a>) {
+        for (idx, line) in text.split('\n').enumerate() {
+            self.lines.push((line.to_string(), Origin { line: origin.line + idx, ..origin }));
+        }
+    }
+
+    /// Appends the output of an included file.
+    pub fn include(&mut self, name: &'a str, other: Output<'a>) {
+        for include in Some(name).into_iter().chain(other.includes) {
+            if !self.includes.contains(&include) {
+                self.includes.push(include);
+            }
+        }
+        self.lines.extend(other.lines);
+    }
+
+    /// Returns the names of the files included directly or indirectly.
+    pub fn includes(&self) -> &[&'a str] {
+        &self.includes
+    }
+
+    /// Removes trailing whitespace, including empty lines, from the end of the output.
+    pub fn trim_end(&mut self) {
+        while let Some(&(ref line, _)) = self.lines.last() {
+            if !line.trim().is_empty() {
+                break;
+            }
+            self.lines.pop();
+        }
+        if let Some(&mut (ref mut line, _)) = self.lines.last_mut() {
+            let len = line.trim_end().len();
+            line.truncate(len);
+        }
+    }
+
+    /// Renders the output as a source string. If `directives` is set, a `#line` directive of that
+    /// form is inserted wherever the origin of a line does not directly follow from the previous
+    /// line. `version` is the version of the final shader, which determines how compilers
+    /// interpret the directives.
+    pub fn render(&self,
+                  directives: Option<LineDirectives>,
+                  version: Version)
+                  -> (String, SourceMap<'a>) {
+        // GLSL ES and GLSL 3.30+ number the line after a #line directive with the given number,
+        // while earlier versions of desktop GLSL number the directive itself, like glslang does
+        let offset = if version.is_es() || version.number >= 330 { 0 } else { 1 };
+
+        let mut source = String::new();
+        let mut map = SourceMap::new(directives.is_some());
+        let mut next: Option<Origin> = None;
+        for &(ref line, origin) in self.lines.iter() {
+            if let Some(directives) = directives {
+                if next != Some(origin) {
+                    let number = origin.line - offset;
+                    match directives {
+                        LineDirectives::FileIds => {
+                            source.push_str(&format!("#line {} {}\n", number, origin.file_id));
+                        }
+                        LineDirectives::FileNames => {
+                            source.push_str(&format!("#line {} \"{}\"\n", number, origin.file));
+                        }
+                    }
+                    map.lines.push(None);
+                }
+            }
+            source.push_str(line);
+            source.push('\n');
+            map.lines.push(Some(origin));
+            next = Some(Origin { line: origin.line + 1, ..origin });
+        }
+        (source, map)
+    }
+}
+
+/// The origin of each line of a rendered source.
+pub struct SourceMap<'a> {
+    /// The origin of each line, or `None` for lines which glossy generated
+    lines: Vec<Option<Origin<'a>>>,
+    line_directives: bool,
+    /// True if the lines have been moved since rendering, so only the files are still known
+    discarded: bool,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(line_directives: bool) -> SourceMap<'a> {
+        SourceMap {
+            lines: Vec::new(),
+            line_directives: line_directives,
+            discarded: false,
+        }
+    }
+
+    /// Accounts for lines which were inserted at the start of the source after it was rendered,
+    /// such as its `#version` and `#extension` directives.
+    pub fn align(&mut self, source: &str) {
+        let inserted = source.lines().count().saturating_sub(self.lines.len());
+        let mut lines = vec![None; inserted];
+        lines.append(&mut self.lines);
+        self.lines = lines;
+    }
+
+    /// Accounts for `count` lines which were inserted before the line at index `idx`, such as
+    /// default precision statements. Every other stage keeps the lines of the body as they are.
+    pub fn insert(&mut self, idx: usize, count: usize) {
+        let idx = cmp::min(idx, self.lines.len());
+        self.lines.splice(idx..idx, vec![None; count]);
+    }
+
+    /// Forgets the origins of the lines, after a stage which moved them. The files are kept, as
+    /// the `#line` directives of the source may still refer to them.
+    pub fn discard_lines(&mut self) {
+        self.discarded = true;
+    }
+
+    /// Rearranges the map after a stage which may have moved lines, given the line each line of
+    /// its output came from. If any line moved, the output no longer has `#line` directives, as
+    /// they would not be correct anymore.
+    pub fn rearrange(&mut self, lines: &[usize]) {
+        if lines.iter().enumerate().any(|(idx, &line)| line != idx + 1) {
+            self.line_directives = false;
+        }
+        self.lines = lines.iter().map(|&line| self.lookup(line)).collect();
+    }
+
+    /// Returns the origin of a line of the source, numbered from 1.
+    pub fn lookup(&self, line: usize) -> Option<Origin<'a>> {
+        if line == 0 || self.discarded {
+            return None;
+        }
+        self.lines.get(line - 1).and_then(|origin| *origin)
+    }
+
+    /// Writes the map as a Rust expression which evaluates to a `&'static
+    /// glossy::source_map::SourceMap`.
+    pub fn write_rust<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut files: Vec<(usize, &str)> = self.lines
+            .iter()
+            .filter_map(|origin| origin.map(|origin| (origin.file_id, origin.file)))
+            .collect();
+        files.sort();
+        files.dedup();
+
+        write!(w, "&::glossy::source_map::SourceMap {{\n    files: &[")?;
+        for (id, name) in files {
+            write!(w, "({}, {:?}), ", id, name)?;
+        }
+        write!(w, "],\n    lines: &[")?;
+        for origin in self.lines.iter().filter(|_| !self.discarded) {
+            match *origin {
+                Some(origin) => write!(w, "Some(({}, {})), ", origin.file_id, origin.line)?,
+                None => write!(w, "None, ")?,
+            }
+        }
+        write!(w,
+               "],\n    line_directives: {},\n}}\n",
+               self.line_directives)
+    }
+}
@@ -0,0 +1,96 @@
+//! User-defined passes over the processed shader sources.
+
+use std::fmt;
+use Language;
+use optimize::SourceKind;
+
+/// A pass over the processed source of a shader, added with `Config::transform`.
+///
+/// Transforms run in the order they were added, on the source built for each target language.
+/// By default a transform runs before optimization, after `#include` directives have been
+/// resolved and the source has been translated for the target language.
+pub trait ShaderTransform {
+    /// Transforms a processed shader source, or fails with the reasons it cannot be built.
+    ///
+    /// Transforms should keep every line of the source where it is, changing lines only in
+    /// place, so that errors can be reported at the original files and lines. A transform which
+    /// returns the same number of lines is assumed to have done so; if the number of lines
+    /// changes, the source map of the shader is discarded.
+    fn transform(&self,
+                 source: &str,
+                 kind: SourceKind,
+                 lang: Language)
+                 -> Result<String, Diagnostics>;
+
+    /// Returns true if the transform should run after optimization instead, on the optimized
+    /// source.
+    fn after_optimization(&self) -> bool {
+        false
+    }
+}
+
+/// A problem found in a shader source by a transform.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// The line of the source passed to the transform, starting from 1, if the problem has one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// The problems which made a transform fail.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Create an empty list of diagnostics.
+    pub fn new() -> Diagnostics {
+        Diagnostics { diagnostics: Vec::new() }
+    }
+
+    /// Add a problem at a line of the source, starting from 1.
+    pub fn error_at<S: Into<String>>(mut self, line: usize, message: S) -> Diagnostics {
+        self.diagnostics.push(Diagnostic {
+            line: Some(line),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Add a problem which is not specific to a line.
+    pub fn error<S: Into<String>>(mut self, message: S) -> Diagnostics {
+        self.diagnostics.push(Diagnostic {
+            line: None,
+            message: message.into(),
+        });
+        self
+    }
+}
+
+impl From<String> for Diagnostics {
+    fn from(message: String) -> Diagnostics {
+        Diagnostics::new().error(message)
+    }
+}
+
+impl<'a> From<&'a str> for Diagnostics {
+    fn from(message: &'a str) -> Diagnostics {
+        Diagnostics::new().error(message)
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, diagnostic) in self.diagnostics.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "\n")?;
+            }
+            match diagnostic.line {
+                Some(line) => write!(f, "{}: {}", line, diagnostic.message)?,
+                None => write!(f, "{}", diagnostic.message)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! Translation of processed shader sources between the desktop GL and GLES dialects of GLSL.
+//!
+//! The translation is purely textual and operates on whole words, so it makes a few assumptions
+//! about the source: interface variables are declared one per line with the storage qualifier at
+//! the start of the line, outside of any function, and a fragment shader written against GLSL
+//! 1.30+/ES 3.00 declares at most one `out` variable. Every line of the source is kept where it
+//! is, so that the source map of the shader still applies once it accounts for the declarations
+//! which are inserted.
+
+use std::collections::HashSet;
+use regex::{Captures, Regex};
+use {Language, insert_after_extensions, lexer, preamble_len};
+use optimize::SourceKind;
+use output::SourceMap;
+use version::{Profile, Version};
+
+/// The two families of GLSL syntax that glossy can translate between.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Dialect {
+    /// GLSL 1.10/1.20 and GLSL ES 1.00 (`attribute`, `varying`, `gl_FragColor`, `texture2D`)
+    Legacy,
+    /// GLSL 1.30+ and GLSL ES 3.00 (`in`, `out`, user-declared outputs, `texture`)
+    Modern,
+}
+
+/// The name of the output variable which replaces `gl_FragColor` in modern fragment shaders.
+const FRAG_COLOR: &str = "glossy_FragColor";
+
+/// Translates a processed shader source written for `from` to the dialect of `to`, returning the
+/// new source and its version.
+///
+/// `source` must begin with the `#version` directive, and `version` is the version it specifies.
+/// Any declarations the translation requires are inserted after the `#extension` directives, and
+/// accounted for in `source_map`.
+pub fn translate(source: &str,
+                 kind: SourceKind,
+                 from: Language,
+                 to: Language,
+                 version: Version,
+                 source_map: &mut SourceMap)
+                 -> Result<(String, Version), String> {
+    let src_dialect = dialect(from, version);
+    let (dst_dialect, dst_version) = destination(src_dialect, to);
+
+    // Split off the #version directive, which is replaced wholesale, and the #extension
+    // directives which follow it
+    let preamble = &source[..preamble_len(source)];
+    let extensions = match preamble.find('\n') {
+        Some(idx) => &preamble[idx + 1..],
+        None => "",
+    };
+    let body = &source[preamble.len()..];
+
+    let header = format!("#version {}\n{}", dst_version, extensions);
+    let mut declarations = String::new();
+    let mut body = match (src_dialect, dst_dialect) {
+        (Dialect::Legacy, Dialect::Modern) => to_modern(body, kind, to, &mut declarations),
+        (Dialect::Modern, Dialect::Legacy) => to_legacy(body, kind)?,
+        _ => body.to_string(),
+    };
+
+    // Precision qualifiers are not allowed in GLSL 1.10/1.20
+    if to == Language::OpenGl && dst_dialect == Dialect::Legacy {
+        body = strip_precision(&body);
+    }
+
+    let source = insert_after_extensions(&(header + &body), &declarations, source_map);
+    Ok((source, dst_version))
+}
+
+/// Returns the version of a source written for `from` once it is translated to `to`.
+pub fn translated_version(from: Language, to: Language, version: Version) -> Version {
+    destination(dialect(from, version), to).1
+}
+
+/// Returns the dialect and version which a source of the given dialect is translated to.
+fn destination(src_dialect: Dialect, to: Language) -> (Dialect, Version) {
+    match to {
+        Language::OpenGlEs20 | Language::WebGl1 => (Dialect::Legacy, Version::new(100, None)),
+        Language::OpenGlEs30 | Language::WebGl2 => {
+            (Dialect::Modern, Version::new(300, Some(Profile::Es)))
+        }
+        Language::OpenGlEs31 => (Dialect::Modern, Version::new(310, Some(Profile::Es))),
+        Language::OpenGlEs32 => (Dialect::Modern, Version::new(320, Some(Profile::Es))),
+        Language::OpenGl33Core => (Dialect::Modern, Version::new(330, Some(Profile::Core))),
+        Language::OpenGl4x => (Dialect::Modern, Version::new(400, Some(Profile::Core))),
+        Language::OpenGl => {
+            match src_dialect {
+                Dialect::Legacy => (Dialect::Legacy, Version::new(120, None)),
+                Dialect::Modern => (Dialect::Modern, Version::new(330, None)),
+            }
+        }
+    }
+}
+
+/// Returns the dialect used by a source of the given language and version.
+fn dialect(lang: Language, version: Version) -> Dialect {
+    let modern = if lang.is_gles() || version.is_es() {
+        version.number >= 300
+    } else {
+        version.number >= 130
+    };
+    if modern {
+        Dialect::Modern
+    } else {
+        Dialect::Legacy
+    }
+}
+
+/// Translates legacy syntax to modern syntax. Declarations which must appear before the body are
+/// appended to `declarations`.
+fn to_modern(body: &str, kind: SourceKind, to: Language, declarations: &mut String) -> String {
+    lazy_static! {
+        static ref LEGACY_RE: Regex = Regex::new(r"\b(attribute|varying|gl_FragColor|texture2DProjLod|texture2DProj|texture2DLod|texture2D|textureCubeLod|textureCube|shadow2D)\b").unwrap();
+    }
+
+    let mut uses_frag_color = false;
+    let body = LEGACY_RE.replace_all(body, |cap: &Captures| {
+        match cap.at(1).unwrap() {
+            "attribute" => "in",
+            "varying" => {
+                if kind == SourceKind::Fragment {
+                    "in"
+                } else {
+                    "out"
+                }
+            }
+            "gl_FragColor" => {
+                uses_frag_color = true;
+                FRAG_COLOR
+            }
+            "texture2DProjLod" => "textureProjLod",
+            "texture2DProj" => "textureProj",
+            "texture2DLod" | "textureCubeLod" => "textureLod",
+            _ => "texture",
+        }
+        .to_string()
+    });
+
+    if uses_frag_color && kind == SourceKind::Fragment {
+        // gl_FragColor is mediump in GLSL ES 1.00
+        let precision = if to.is_gles() { "mediump " } else { "" };
+        declarations.push_str(&format!("out {}vec4 {};\n", precision, FRAG_COLOR));
+    }
+    body
+}
+
+/// Translates modern syntax to legacy syntax.
+fn to_legacy(body: &str, kind: SourceKind) -> Result<String, String> {
+    lazy_static! {
+        static ref LAYOUT_RE: Regex = Regex::new(r"^([ \t]*)layout\s*\([^)]*\)\s*").unwrap();
+        static ref IN_OUT_RE: Regex = Regex::new(r"^([ \t]*)(in|out)\b").unwrap();
+        static ref FRAG_OUT_RE: Regex = Regex::new(r"^[ \t]*out\s+(?:(?:lowp|mediump|highp)\s+)?vec4\s+(\w+)\s*;").unwrap();
+        static ref CUBE_RE: Regex = Regex::new(r"\bsamplerCube\s+(\w+)").unwrap();
+        static ref MODERN_RE: Regex = Regex::new(r"\b(textureProjLod|textureProj|textureLod|texture)\s*\(\s*(\w*)").unwrap();
+    }
+
+    // Rewrite the global declarations, removing the fragment output declaration
+    let mut outputs = Vec::new();
+    let body = map_global_lines(body, |line| {
+        let line = LAYOUT_RE.replace(line, "$1");
+        if kind == SourceKind::Fragment {
+            if let Some(cap) = FRAG_OUT_RE.captures(&line) {
+                outputs.push(cap.at(1).unwrap().to_string());
+                return FRAG_OUT_RE.replace(&line, "");
+            }
+        }
+        IN_OUT_RE.replace(&line, |cap: &Captures| {
+            let qualifier = match (kind, cap.at(2).unwrap()) {
+                (SourceKind::Fragment, _) => "varying",
+                (_, "in") => "attribute",
+                _ => "varying",
+            };
+            format!("{}{}", cap.at(1).unwrap(), qualifier)
+        })
+    });
+
+    // Replace the fragment output with gl_FragColor
+    if outputs.len() > 1 {
+        return Err(format!("cannot translate fragment shader with multiple outputs ({}) to a \
+                            single gl_FragColor",
+                           outputs.join(", ")));
+    }
+    let body = match outputs.first() {
+        Some(output) => {
+            let output_re = Regex::new(&format!(r"\b{}\b", output)).unwrap();
+            output_re.replace_all(&body, "gl_FragColor")
+        }
+        None => body,
+    };
+
+    // texture() is overloaded in modern GLSL, so use the sampler's declaration to pick between
+    // the 2D and cube map variants. Anything other than a samplerCube is assumed to be 2D.
+    let cubes: HashSet<String> = CUBE_RE.captures_iter(&body)
+        .map(|cap| cap.at(1).unwrap().to_string())
+        .collect();
+    let body = MODERN_RE.replace_all(&body, |cap: &Captures| {
+        let sampler = cap.at(2).unwrap();
+        let cube = cubes.contains(sampler);
+        let function = match cap.at(1).unwrap() {
+            "textureProjLod" => "texture2DProjLod",
+            "textureProj" => "texture2DProj",
+            "textureLod" if cube => "textureCubeLod",
+            "textureLod" => "texture2DLod",
+            _ if cube => "textureCube",
+            _ => "texture2D",
+        };
+        format!("{}({}", function, sampler)
+    });
+    Ok(body)
+}
+
+/// Rewrites every logical line of a source which begins at global scope, outside of any braces
+/// or parentheses, with `f`. Every line terminator is kept, so that the lines of the source stay
+/// where they are.
+fn map_global_lines<F>(body: &str, mut f: F) -> String
+    where F: FnMut(&str) -> String
+{
+    let mut output = String::with_capacity(body.len());
+    let mut pos = 0;
+    let mut depth = 0;
+    for line in lexer::lines(body) {
+        let start = line.text.as_ptr() as usize - body.as_ptr() as usize;
+        output.push_str(&body[pos..start]);
+        if depth == 0 {
+            output.push_str(&f(line.text));
+        } else {
+            output.push_str(line.text);
+        }
+        pos = start + line.text.len();
+        for c in line.code.chars() {
+            match c {
+                '{' | '(' => depth += 1,
+                '}' | ')' if depth > 0 => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    output.push_str(&body[pos..]);
+    output
+}
+
+/// Removes precision statements and qualifiers from a source.
+fn strip_precision(body: &str) -> String {
+    lazy_static! {
+        static ref STATEMENT_RE: Regex = Regex::new(r"\bprecision\s+(?:lowp|mediump|highp)\s+\w+\s*;").unwrap();
+        static ref QUALIFIER_RE: Regex = Regex::new(r"\b(?:lowp|mediump|highp)\s+").unwrap();
+    }
+
+    let body = STATEMENT_RE.replace_all(body, "");
+    QUALIFIER_RE.replace_all(&body, "")
+}
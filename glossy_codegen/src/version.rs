@@ -0,0 +1,71 @@
+//! GLSL version numbers and profiles, as specified by `#version` directives.
+
+use std::fmt;
+
+/// The profile following the version number in a `#version` directive.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Profile {
+    Core,
+    Compatibility,
+    Es,
+}
+
+/// A GLSL version, e.g. `330 core` or `300 es`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Version {
+    pub number: u32,
+    pub profile: Option<Profile>,
+}
+
+impl Version {
+    pub fn new(number: u32, profile: Option<Profile>) -> Version {
+        Version {
+            number: number,
+            profile: profile,
+        }
+    }
+
+    /// Parses the arguments of a `#version` directive.
+    pub fn parse(number: &str, profile: Option<&str>) -> Option<Version> {
+        let number = match number.parse() {
+            Ok(number) => number,
+            Err(_) => return None,
+        };
+        let profile = match profile {
+            Some("core") => Some(Profile::Core),
+            Some("compatibility") => Some(Profile::Compatibility),
+            Some("es") => Some(Profile::Es),
+            Some(_) => return None,
+            None => None,
+        };
+        Some(Version::new(number, profile))
+    }
+
+    /// Returns true if this is a GLSL ES version. Version 100 is always GLSL ES, and takes no
+    /// profile.
+    pub fn is_es(&self) -> bool {
+        self.number == 100 || self.profile == Some(Profile::Es)
+    }
+
+    /// Returns the profile in effect for this version. Desktop GLSL 1.50 and above default to
+    /// the core profile when none is specified.
+    pub fn effective_profile(&self) -> Option<Profile> {
+        match self.profile {
+            Some(profile) => Some(profile),
+            None if self.is_es() => Some(Profile::Es),
+            None if self.number >= 150 => Some(Profile::Core),
+            None => None,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.profile {
+            Some(Profile::Core) => write!(f, "{} core", self.number),
+            Some(Profile::Compatibility) => write!(f, "{} compatibility", self.number),
+            Some(Profile::Es) => write!(f, "{} es", self.number),
+            None => write!(f, "{}", self.number),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Validation of shader sources against the additional restrictions imposed by WebGL.
+//!
+//! Each source file is checked separately so that errors can point at the original file and
+//! line, before `#include` directives are resolved. Names which are visible across files
+//! (uniforms, constants and macros) are gathered from the whole include tree first.
+
+use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use Language;
+use lexer::strip_comments;
+use optimize::SourceKind;
+
+/// Extensions which WebGL 1 exposes to shaders, as listed in the WebGL extension registry
+/// (including draft extensions).
+const WEBGL1_EXTENSIONS: &[&str] = &["GL_OES_standard_derivatives",
+                                     "GL_EXT_frag_depth",
+                                     "GL_EXT_draw_buffers",
+                                     "GL_EXT_shader_texture_lod",
+                                     "GL_EXT_blend_func_extended",
+                                     "GL_ANGLE_multi_draw"];
+
+/// Extensions which WebGL 2 exposes to shaders, as listed in the WebGL extension registry
+/// (including draft extensions).
+const WEBGL2_EXTENSIONS: &[&str] = &["GL_OVR_multiview2",
+                                     "GL_EXT_clip_cull_distance",
+                                     "GL_ANGLE_clip_cull_distance",
+                                     "GL_EXT_conservative_depth",
+                                     "GL_EXT_blend_func_extended",
+                                     "GL_ANGLE_multi_draw",
+                                     "GL_ANGLE_base_vertex_base_instance",
+                                     "GL_OES_sample_variables",
+                                     "GL_OES_shader_multisample_interpolation",
+                                     "GL_NV_shader_noperspective_interpolation"];
+
+/// Identifiers which may appear in a constant expression besides constants and macros.
+const CONSTRUCTORS: &[&str] = &["int", "float"];
+
+/// Validates a shader source and every file it includes against the WebGL restrictions for
+/// `lang`, returning a list of errors of the form `file:line: message`.
+pub fn validate(lang: Language,
+                kind: SourceKind,
+                name: &str,
+                source: &str,
+                includes: &HashMap<String, String>)
+                -> Vec<String> {
+    // Gather every file in the include tree, without comments
+    let mut files = Vec::new();
+    collect_files(name, source, includes, &mut files);
+
+    // Gather the names visible across files
+    let mut uniforms = HashSet::new();
+    let mut constants = HashSet::new();
+    for &(_, ref source) in files.iter() {
+        gather_names(source, &mut uniforms, &mut constants);
+    }
+
+    let mut errors = Vec::new();
+    for &(name, ref source) in files.iter() {
+        check_extensions(lang, name, source, &mut errors);
+        if lang == Language::WebGl1 {
+            check_loops_and_indexing(kind, name, source, &uniforms, &constants, &mut errors);
+        }
+    }
+    errors
+}
+
+/// Validates that a processed fragment shader declares a default float precision, which GLSL ES
+/// requires and WebGL implementations enforce.
+pub fn validate_precision(kind: SourceKind, name: &str, source: &str) -> Option<String> {
+    lazy_static! {
+        static ref PRECISION_RE: Regex = Regex::new(r"\bprecision\s+(?:lowp|mediump|highp)\s+float\s*;").unwrap();
+    }
+
+    if kind == SourceKind::Fragment && !PRECISION_RE.is_match(&strip_comments(source)) {
+        Some(format!("{}: fragment shader does not declare a default float precision; add a \
+                      precision statement or use Config::default_precision()",
+                     name))
+    } else {
+        None
+    }
+}
+
+/// Recursively collects a file and the files it includes, each included only once.
+fn collect_files<'a>(name: &'a str,
+                     source: &str,
+                     includes: &'a HashMap<String, String>,
+                     files: &mut Vec<(&'a str, String)>) {
+    lazy_static! {
+        static ref INCLUDE_RE: Regex = Regex::new("(?m)^\\s*#\\s*include\\s+[\"<]([:print:]+)[\">]\\s*$").unwrap();
+    }
+
+    let source = strip_comments(source);
+    let mut included = Vec::new();
+    for cap in INCLUDE_RE.captures_iter(&source) {
+        // Missing files are reported by the include pass itself
+        if let Some((name, _)) = includes.get_key_value(cap.at(1).unwrap()) {
+            included.push(name);
+        }
+    }
+    files.push((name, source));
+    for include_name in included {
+        if !files.iter().any(|&(name, _)| name == include_name.as_str()) {
+            collect_files(include_name, &includes[include_name], includes, files);
+        }
+    }
+}
+
+/// Collects the names of uniforms, constants and macros declared in a source.
+fn gather_names(source: &str, uniforms: &mut HashSet<String>, constants: &mut HashSet<String>) {
+    lazy_static! {
+        static ref UNIFORM_RE: Regex = Regex::new(r"\buniform\s+(?:(?:lowp|mediump|highp)\s+)?\w+\s+(\w+)").unwrap();
+        static ref CONST_RE: Regex = Regex::new(r"\bconst\s+(?:(?:lowp|mediump|highp)\s+)?\w+\s+(\w+)").unwrap();
+        static ref DEFINE_RE: Regex = Regex::new(r"(?m)^\s*#\s*define\s+(\w+)").unwrap();
+    }
+
+    for cap in UNIFORM_RE.captures_iter(source) {
+        uniforms.insert(cap.at(1).unwrap().to_string());
+    }
+    for cap in CONST_RE.captures_iter(source).chain(DEFINE_RE.captures_iter(source)) {
+        constants.insert(cap.at(1).unwrap().to_string());
+    }
+}
+
+/// Checks that every `#extension` directive names an extension available in WebGL.
+fn check_extensions(lang: Language, name: &str, source: &str, errors: &mut Vec<String>) {
+    lazy_static! {
+        static ref EXTENSION_RE: Regex = Regex::new(r"(?m)^\s*#\s*extension\s+(\w+)").unwrap();
+    }
+
+    let allowed = match lang {
+        Language::WebGl1 => WEBGL1_EXTENSIONS,
+        _ => WEBGL2_EXTENSIONS,
+    };
+    for cap in EXTENSION_RE.captures_iter(source) {
+        let extension = cap.at(1).unwrap();
+        if extension != "all" && !allowed.contains(&extension) {
+            errors.push(format!("{}:{}: extension {} is not available in {:?}",
+                                name,
+                                line_of(source, cap.pos(0).unwrap().0),
+                                extension,
+                                lang));
+        }
+    }
+}
+
+/// Checks the loop and array indexing restrictions of GLSL ES 1.00 Appendix A, which WebGL 1
+/// enforces.
+fn check_loops_and_indexing(kind: SourceKind,
+                            name: &str,
+                            source: &str,
+                            uniforms: &HashSet<String>,
+                            constants: &HashSet<String>,
+                            errors: &mut Vec<String>) {
+    lazy_static! {
+        static ref WHILE_RE: Regex = Regex::new(r"\b(while|do)\b").unwrap();
+        static ref FOR_RE: Regex = Regex::new(r"\bfor\s*\(([^;]*);([^;]*);([^)]*)\)").unwrap();
+        static ref INIT_RE: Regex = Regex::new(r"^\s*(?:(?:lowp|mediump|highp)\s+)?(?:int|float)\s+(\w+)\s*=(.+)$").unwrap();
+        static ref COND_RE: Regex = Regex::new(r"^\s*(\w+)\s*(?:<=|>=|==|!=|<|>)(.+)$").unwrap();
+        static ref STEP_RE: Regex = Regex::new(r"^\s*(?:(\w+)\s*(?:\+\+|--)|(?:\+\+|--)\s*(\w+)|(\w+)\s*(?:\+=|-=)(.+))\s*$").unwrap();
+        static ref INDEX_RE: Regex = Regex::new(r"\b(\w+)\s*\[([^\[\]]*)\]").unwrap();
+    }
+
+    for cap in WHILE_RE.captures_iter(source) {
+        errors.push(format!("{}:{}: {} loops are not supported by WebGL 1",
+                            name,
+                            line_of(source, cap.pos(0).unwrap().0),
+                            cap.at(1).unwrap()));
+    }
+
+    let mut loop_indices = HashSet::new();
+    for cap in FOR_RE.captures_iter(source) {
+        let line = line_of(source, cap.pos(0).unwrap().0);
+        let mut error = |message: &str| errors.push(format!("{}:{}: {}", name, line, message));
+
+        let index = match INIT_RE.captures(cap.at(1).unwrap()) {
+            Some(init) => {
+                if !is_constant(init.at(2).unwrap(), constants) {
+                    error("loop index must be initialized with a constant expression");
+                }
+                init.at(1).unwrap().to_string()
+            }
+            None => {
+                error("loop index must be declared as an int or float in the for statement");
+                continue;
+            }
+        };
+        match COND_RE.captures(cap.at(2).unwrap()) {
+            Some(ref cond) if cond.at(1).unwrap() == index &&
+                              is_constant(cond.at(2).unwrap(), constants) => {}
+            _ => {
+                error(&format!("loop condition must compare \"{}\" with a constant expression",
+                               index))
+            }
+        }
+        let step_ok = match STEP_RE.captures(cap.at(3).unwrap()) {
+            Some(step) => {
+                match (step.at(1), step.at(2), step.at(3)) {
+                    (Some(i), _, _) | (_, Some(i), _) => i == index,
+                    (_, _, Some(i)) => i == index && is_constant(step.at(4).unwrap(), constants),
+                    _ => false,
+                }
+            }
+            None => false,
+        };
+        if !step_ok {
+            error(&format!("loop expression must increment or decrement \"{}\" by a constant",
+                           index));
+        }
+        loop_indices.insert(index);
+    }
+
+    for cap in INDEX_RE.captures_iter(source) {
+        let array = cap.at(1).unwrap();
+        // Uniform arrays may be indexed arbitrarily in vertex shaders
+        if kind == SourceKind::Vertex && uniforms.contains(array) {
+            continue;
+        }
+        let is_index = |ident: &str| loop_indices.contains(ident) || constants.contains(ident);
+        if !identifiers(cap.at(2).unwrap())
+            .into_iter()
+            .all(|ident| is_index(ident) || CONSTRUCTORS.contains(&ident)) {
+            errors.push(format!("{}:{}: \"{}\" may only be indexed by constant expressions and \
+                                 loop indices in WebGL 1",
+                                name,
+                                line_of(source, cap.pos(0).unwrap().0),
+                                array));
+        }
+    }
+}
+
+/// Returns true if an expression only refers to constants, macros and literals.
+fn is_constant(expr: &str, constants: &HashSet<String>) -> bool {
+    identifiers(expr)
+        .into_iter()
+        .all(|ident| constants.contains(ident) || CONSTRUCTORS.contains(&ident))
+}
+
+/// Returns the identifiers in an expression.
+fn identifiers(expr: &str) -> Vec<&str> {
+    lazy_static! {
+        static ref IDENT_RE: Regex = Regex::new(r"\b[A-Za-z_]\w*").unwrap();
+    }
+
+    IDENT_RE.find_iter(expr).map(|(start, end)| &expr[start..end]).collect()
+}
+
+/// Returns the 1-based line number of a byte offset in a source.
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
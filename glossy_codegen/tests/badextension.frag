@@ -0,0 +1,7 @@
+#version 100
+#include "extension1.glsl"
+#include "extension3.glsl"
+
+void main() {
+    gl_FragColor = vec4(edge(0.5));
+}
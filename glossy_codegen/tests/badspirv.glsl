@@ -0,0 +1,3 @@
+vec2 offset() {
+    return vec2(0.0, 0.0;
+}
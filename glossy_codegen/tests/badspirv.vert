@@ -0,0 +1,7 @@
+#version 450
+
+#include "badspirv.glsl"
+
+void main() {
+    gl_Position = vec4(offset(), 0.0, 1.0);
+}
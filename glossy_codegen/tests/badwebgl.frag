@@ -0,0 +1,14 @@
+#version 100
+#extension GL_ARB_gpu_shader5 : enable
+precision mediump float;
+
+uniform vec4 colors[4];
+uniform int count;
+
+void main() {
+    vec4 sum = vec4(0.0);
+    for (int i = 0; i < count; i++) {
+        sum += colors[count - 1];
+    }
+    gl_FragColor = sum;
+}
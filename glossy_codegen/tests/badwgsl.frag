@@ -0,0 +1,9 @@
+#version 450
+
+#include "badwgsl.glsl"
+
+layout(location = 0) out vec4 color;
+
+void main() {
+    color = tint();
+}
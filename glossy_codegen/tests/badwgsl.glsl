@@ -0,0 +1,4 @@
+vec4 tint() {
+    vec4 c = vec4(1.0);
+    return c + undefined;
+}
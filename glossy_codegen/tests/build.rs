@@ -1,5 +1,7 @@
+extern crate glossy;
 extern crate glossy_codegen;
-use glossy_codegen::{Config, Language};
+use glossy_codegen::{Config, Diagnostics, Language, LineDirectives, OptimizerOptions, Precision,
+                     ShaderTransform, SourceKind, VersionPolicy};
 
 fn setup() {
     use std::{env, fs};
@@ -31,6 +33,684 @@ fn optimize() {
         .build();
 }
 
+#[test]
+fn version_profile() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2a (the profile of the #version directive is preserved)
+    Config::new(Language::OpenGlEs30)
+        .fragment("tests/profile.frag")
+        .include("tests/version100.glsl")
+        .version_policy(VersionPolicy::NotNewer)
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("profile.frag")).unwrap();
+    assert!(source.starts_with("#version 300 es\n"));
+}
+
+#[test]
+fn optimize_info() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2b (reflection info is written alongside optimized output)
+    Config::new(Language::OpenGl)
+        .vertex("tests/info.vert")
+        .include("tests/include*.glsl")
+        .optimize()
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let info = fs::read_to_string(Path::new(&out_dir).join("info.vert.info.rs")).unwrap();
+    assert!(info.starts_with("&::glossy::reflect::ShaderInfo {"));
+    assert_eq!(info_variables(&info, "inputs"),
+               vec![("position".to_string(), "Float".to_string(), 2)]);
+    assert_eq!(info_variables(&info, "uniforms"),
+               vec![("common_uniform".to_string(), "Float".to_string(), 1)]);
+    assert!(info_variables(&info, "textures").is_empty());
+}
+
+#[test]
+fn optimize_options() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2c (optimized output with non-default optimizer options; functions which are never
+    // called are kept in sources which are not full shaders)
+    Config::new(Language::OpenGl)
+        .vertex("tests/options.vert")
+        .include("tests/include*.glsl")
+        .optimize()
+        .optimizer_options(OptimizerOptions::new()
+            .preprocess(true)
+            .full_shader(false)
+            .max_unroll_iterations(8))
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let path = Path::new(&out_dir).join("options.vert");
+    assert!(fs::read_to_string(&path).unwrap().contains("unused_helper"));
+
+    // The minifier respects the same option
+    #[cfg(feature = "minifier")]
+    {
+        use glossy_codegen::OptimizerBackend;
+
+        Config::new(Language::OpenGl)
+            .vertex("tests/options.vert")
+            .include("tests/include*.glsl")
+            .optimizer_backend(OptimizerBackend::Minifier)
+            .build();
+        assert!(!fs::read_to_string(&path).unwrap().contains("unused_helper"));
+
+        Config::new(Language::OpenGl)
+            .vertex("tests/options.vert")
+            .include("tests/include*.glsl")
+            .optimizer_backend(OptimizerBackend::Minifier)
+            .optimizer_options(OptimizerOptions::new().full_shader(false))
+            .build();
+        assert!(fs::read_to_string(&path).unwrap().contains("unused_helper"));
+    }
+}
+
+#[test]
+fn precision() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2d (default precision statements inserted after #version for GLES)
+    Config::new(Language::OpenGlEs20)
+        .fragment("tests/precision.frag")
+        .include("tests/include*.glsl")
+        .default_precision(Precision::Medium, Precision::High)
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("precision.frag")).unwrap();
+    assert!(source.starts_with("#version 100\nprecision mediump float;\nprecision highp int;\n"));
+
+    // The statements follow conditional #extension directives, and the lines after them keep
+    // their numbers
+    Config::new(Language::OpenGlEs30)
+        .fragment("tests/precision_extension.frag")
+        .default_precision(Precision::Medium, Precision::High)
+        .build();
+
+    let source =
+        fs::read_to_string(Path::new(&out_dir).join("precision_extension.frag")).unwrap();
+    let map =
+        fs::read_to_string(Path::new(&out_dir).join("precision_extension.frag.map.rs")).unwrap();
+    let lines: Vec<&str> = source.lines().collect();
+    let endif = lines.iter().position(|&line| line == "#endif").unwrap();
+    assert_eq!(lines[endif + 1], "precision mediump float;");
+    let body = source.find("precision highp int;\n").unwrap() + "precision highp int;\n".len();
+    check_lines(&source[body..], &map, false);
+    assert_eq!(map.matches("None").count() + map.matches("Some(").count(), lines.len());
+
+    // Explicitly-declared precisions are kept by the optimizer
+    Config::new(Language::OpenGlEs20)
+        .fragment("tests/precision.frag")
+        .include("tests/include*.glsl")
+        .default_precision(Precision::Medium, Precision::High)
+        .optimize()
+        .build();
+
+    let info = fs::read_to_string(Path::new(&out_dir).join("precision.frag.info.rs")).unwrap();
+    let tint = info.lines().find(|line| line.contains("name: \"tint\"")).unwrap();
+    assert!(tint.contains("precision: ::glossy::reflect::Precision::Low,"));
+}
+
+#[test]
+fn translate() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2e (desktop GLSL 1.20 translated to GLSL ES 3.00)
+    Config::new(Language::OpenGlEs30)
+        .source("tests/translate.*")
+        .translate_from(Language::OpenGl)
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let vert = fs::read_to_string(Path::new(&out_dir).join("translate.vert")).unwrap();
+    assert!(vert.starts_with("#version 300 es\n"));
+    assert!(vert.contains("in vec2 position;"));
+    assert!(vert.contains("out vec2 uv;"));
+    let frag = fs::read_to_string(Path::new(&out_dir).join("translate.frag")).unwrap();
+    assert!(frag.starts_with("#version 300 es\nout mediump vec4 glossy_FragColor;\n"));
+    assert!(frag.contains("in vec2 uv;"));
+    assert!(frag.contains("glossy_FragColor = texture(tex, uv);"));
+
+    // Test 2f (GLSL ES 3.00 translated to desktop GLSL 3.30)
+    Config::new(Language::OpenGl)
+        .source("tests/translate_es.frag")
+        .translate_from(Language::OpenGlEs30)
+        .build();
+
+    let frag = fs::read_to_string(Path::new(&out_dir).join("translate_es.frag")).unwrap();
+    assert!(frag.starts_with("#version 330\n"));
+
+    // Test 2g (GLSL ES 3.00 translated to GLSL ES 1.00)
+    Config::new(Language::OpenGlEs20)
+        .source("tests/translate_es.frag")
+        .translate_from(Language::OpenGlEs30)
+        .build();
+
+    let frag = fs::read_to_string(Path::new(&out_dir).join("translate_es.frag")).unwrap();
+    assert!(frag.starts_with("#version 100\n"));
+    assert!(frag.contains("varying vec2 uv;"));
+    assert!(!frag.contains("out vec4 color;"));
+    assert!(frag.contains("gl_FragColor = texture2D(tex, uv) + textureCube(env, vec3(uv, 1.0));"));
+
+    // Function parameters are kept, and every line stays where it is
+    Config::new(Language::OpenGlEs30)
+        .source("tests/translate_params.frag")
+        .build();
+    let original = fs::read_to_string(Path::new(&out_dir).join("translate_params.frag")).unwrap();
+    Config::new(Language::OpenGlEs20)
+        .source("tests/translate_params.frag")
+        .translate_from(Language::OpenGlEs30)
+        .build();
+
+    let frag = fs::read_to_string(Path::new(&out_dir).join("translate_params.frag")).unwrap();
+    assert!(frag.contains("varying vec2 uv;"));
+    assert!(!frag.contains("out vec4 color;"));
+    assert!(frag.contains("vec4 shade(\n    in vec4 base,\n    out float alpha) {"));
+    assert!(frag.contains("gl_FragColor = shade("));
+    let shade = |source: &str| source.lines().position(|line| line.starts_with("vec4 shade("));
+    assert_eq!(shade(&frag), shade(&original));
+    assert_eq!(frag.lines().count(), original.lines().count());
+
+    // The output declaration follows conditional #extension directives
+    Config::new(Language::OpenGlEs30)
+        .source("tests/translate_extension.frag")
+        .translate_from(Language::OpenGlEs20)
+        .build();
+
+    let frag = fs::read_to_string(Path::new(&out_dir).join("translate_extension.frag")).unwrap();
+    let map =
+        fs::read_to_string(Path::new(&out_dir).join("translate_extension.frag.map.rs")).unwrap();
+    let lines: Vec<&str> = frag.lines().collect();
+    let endif = lines.iter().position(|&line| line == "#endif").unwrap();
+    assert_eq!(lines[endif + 1], "out mediump vec4 glossy_FragColor;");
+    assert_eq!(lines[endif + 2],
+               format!("#line 5 {}", file_id(&map, "translate_extension.frag")));
+    assert_eq!(map.matches("None").count() + map.matches("Some(").count(), lines.len());
+}
+
+#[test]
+fn targets() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2h (one output per additional target, in separate subdirectories)
+    Config::new(Language::OpenGl)
+        .source("tests/targets.*")
+        .translate_from(Language::OpenGl)
+        .targets(&[Language::OpenGlEs20, Language::OpenGlEs30])
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_path = Path::new(&out_dir);
+    let read = |path: &str| fs::read_to_string(out_path.join(path)).unwrap();
+    assert!(read("targets.frag").starts_with("#version 120\n"));
+    assert!(read("gles20/targets.frag").starts_with("#version 100\n"));
+    assert!(read("gles30/targets.frag").starts_with("#version 300 es\n"));
+    let select = read("targets.frag.targets.rs");
+    assert!(select.contains("::glossy::Language::OpenGl => \
+                             Some(include_str!(concat!(env!(\"OUT_DIR\"), \"/targets.frag\"))),"));
+    assert!(select.contains("::glossy::Language::OpenGlEs20 => "));
+    assert!(select.contains("::glossy::Language::OpenGlEs30 => "));
+}
+
+#[test]
+fn default_version() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2i (version fallback for shaders without a #version directive)
+    Config::new(Language::OpenGl33Core)
+        .vertex("tests/core.vert")
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("core.vert")).unwrap();
+    assert!(source.starts_with("#version 330 core\n"));
+
+    // GLSL ES shaders without a #version directive are GLSL ES 1.00
+    Config::new(Language::OpenGlEs30)
+        .vertex("tests/core.vert")
+        .build();
+
+    let source = fs::read_to_string(Path::new(&out_dir).join("core.vert")).unwrap();
+    assert!(source.starts_with("#version 100\n"));
+}
+
+#[test]
+#[should_panic]
+fn target_version() {
+    setup();
+
+    // Test 2j (GLSL 1.20 is not accepted by a core profile)
+    Config::new(Language::OpenGl4x)
+        .fragment("tests/badversion.frag")
+        .build();
+}
+
+#[test]
+fn webgl() {
+    setup();
+
+    // Test 2k (shader within the WebGL 1 restrictions)
+    Config::new(Language::WebGl1)
+        .fragment("tests/webgl.frag")
+        .include("tests/webgl_common.glsl")
+        .default_precision(Precision::Medium, Precision::Medium)
+        .build();
+
+    // Extensions from the WebGL extension registry are accepted by both versions
+    Config::new(Language::WebGl1)
+        .vertex("tests/webgl_multi_draw.vert")
+        .targets(&[Language::WebGl2])
+        .build();
+}
+
+#[test]
+#[should_panic(expected = "badwebgl.frag:2: extension GL_ARB_gpu_shader5 is not available in \
+                           WebGl1\n\
+                           badwebgl.frag:10: loop condition must compare \"i\" with a constant \
+                           expression\n\
+                           badwebgl.frag:11: \"colors\" may only be indexed by constant \
+                           expressions")]
+fn webgl_restrictions() {
+    setup();
+
+    // Test 2l (extension, loop condition and dynamic indexing violations)
+    Config::new(Language::WebGl1)
+        .fragment("tests/badwebgl.frag")
+        .build();
+}
+
+#[test]
+#[should_panic(expected = "webgl2.frag: fragment shader does not declare a default float \
+                           precision")]
+fn webgl_precision() {
+    setup();
+
+    // Test 2m (fragment shader without a default float precision)
+    Config::new(Language::WebGl2)
+        .fragment("tests/webgl2.frag")
+        .build();
+}
+
+#[test]
+fn extension() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2n (#extension directives from includes are merged and hoisted after #version)
+    Config::new(Language::OpenGlEs20)
+        .fragment("tests/extension.frag")
+        .include("tests/extension*.glsl")
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("extension.frag")).unwrap();
+    let map = fs::read_to_string(Path::new(&out_dir).join("extension.frag.map.rs")).unwrap();
+    assert!(source.starts_with(&format!("#version 100\n\
+                                         #extension GL_OES_standard_derivatives : require\n\
+                                         #extension GL_EXT_shader_texture_lod : enable\n\
+                                         #line 1 {}\n",
+                                        file_id(&map, "extension1.glsl"))));
+    assert_eq!(source.matches("#extension").count(), 2);
+
+    // Conditional #extension directives, and those of files included conditionally, are left in
+    // place
+    Config::new(Language::OpenGlEs20)
+        .fragment("tests/conditional_extension.frag")
+        .include("tests/extension*.glsl")
+        .build();
+
+    let source =
+        fs::read_to_string(Path::new(&out_dir).join("conditional_extension.frag")).unwrap();
+    let lines: Vec<&str> = source.lines().collect();
+    assert!(!lines[1].starts_with("#extension"));
+    let ifdef = lines.iter().position(|&line| line == "#ifdef GL_EXT_shader_texture_lod");
+    let extension = lines.iter()
+        .position(|&line| line == "#extension GL_EXT_shader_texture_lod : enable");
+    assert_eq!(extension, ifdef.map(|ifdef| ifdef + 1));
+    let ifdef = lines.iter().position(|&line| line == "#ifdef GL_OES_standard_derivatives");
+    let extension = lines.iter()
+        .position(|&line| line == "#extension GL_OES_standard_derivatives : enable");
+    assert!(ifdef.is_some() && extension > ifdef);
+}
+
+#[test]
+#[should_panic]
+fn extension_conflict() {
+    setup();
+
+    // Test 2o (an extension both enabled and disabled)
+    Config::new(Language::OpenGlEs20)
+        .fragment("tests/badextension.frag")
+        .include("tests/extension*.glsl")
+        .build();
+}
+
+#[test]
+fn comments() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2p (comments around directives, comment markers inside comments, and line
+    // continuations)
+    Config::new(Language::OpenGl)
+        .vertex("tests/comments.vert")
+        .include("tests/include*.glsl")
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let path = Path::new(&out_dir).join("comments.vert");
+    let source = fs::read_to_string(&path).unwrap();
+    let map = fs::read_to_string(Path::new(&out_dir).join("comments.vert.map.rs")).unwrap();
+    assert!(source.contains("/* include 2 */\n#line 3 "));
+    assert!(source.contains("// /* h\n#define SCALE \\\n"));
+    check_lines(&source, &map, true);
+
+    Config::new(Language::OpenGl)
+        .vertex("tests/comments.vert")
+        .include("tests/include*.glsl")
+        .discard_line_info()
+        .build();
+
+    let source = fs::read_to_string(&path).unwrap();
+    assert!(source.contains("#define SCALE     2.0\n"));
+    assert!(!source.contains("block_dummy"));
+    assert!(!source.contains("/*"));
+}
+
+#[test]
+fn line_directives() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2q (every line following a #line directive matches the original line, with file ids
+    // and with GL_GOOGLE_cpp_style_line_directive file names)
+    Config::new(Language::OpenGl)
+        .vertex("tests/lines.vert")
+        .include("tests/include*.glsl")
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let path = Path::new(&out_dir).join("lines.vert");
+    let source = fs::read_to_string(&path).unwrap();
+    let map = fs::read_to_string(Path::new(&out_dir).join("lines.vert.map.rs")).unwrap();
+    check_lines(&source, &map, true);
+
+    Config::new(Language::OpenGl)
+        .vertex("tests/lines.vert")
+        .include("tests/include*.glsl")
+        .line_directives(LineDirectives::FileNames)
+        .build();
+
+    let source = fs::read_to_string(&path).unwrap();
+    assert!(source.starts_with("#version 120\n\
+                                #extension GL_EXT_gpu_shader4 : enable\n\
+                                #extension GL_GOOGLE_cpp_style_line_directive : require\n\
+                                #line 0 \"lines.vert\"\n"));
+    check_lines(&source, "", true);
+}
+
+#[test]
+fn source_map() {
+    use std::{env, fs};
+    use std::path::Path;
+    use glossy::source_map::SourceMap;
+
+    setup();
+
+    // Test 2r (source map of a shader without #line directives, with its own file id, and driver
+    // log translation)
+    Config::new(Language::OpenGl)
+        .vertex("tests/source_map.vert")
+        .include("tests/include*.glsl")
+        .discard_line_info()
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let map = fs::read_to_string(Path::new(&out_dir).join("source_map.vert.map.rs")).unwrap();
+    let (shader, include) = (file_id(&map, "source_map.vert"), file_id(&map, "include1.glsl"));
+    assert!(shader != 0 && shader != include);
+    assert!(map.contains(&format!("lines: &[None, Some(({1}, 1)), Some(({1}, 3)), \
+                                   Some(({1}, 4)), Some(({1}, 5)), Some(({0}, 5)), \
+                                   Some(({0}, 6)), Some(({0}, 7)), ]",
+                                  shader,
+                                  include)));
+    assert!(map.contains("line_directives: false"));
+
+    static MAP: SourceMap = SourceMap {
+        files: &[(0, "source_map.vert"), (1, "include1.glsl")],
+        lines: &[None, Some((1, 1)), Some((0, 6))],
+        line_directives: false,
+    };
+    assert_eq!(MAP.translate_log("0:3(10): error: `foo' undeclared"),
+               "source_map.vert:6(10): error: `foo' undeclared");
+    assert_eq!(MAP.translate_log("0(2) : error C1008: undefined variable \"foo\""),
+               "include1.glsl(1) : error C1008: undefined variable \"foo\"");
+    assert_eq!(MAP.translate_log("ERROR: 0:3: 'foo' : undeclared identifier\n\
+                                  ERROR: 0:9: '' : compilation terminated"),
+               "ERROR: source_map.vert:6: 'foo' : undeclared identifier\n\
+                ERROR: 0:9: '' : compilation terminated");
+}
+
+#[test]
+fn driver_log() {
+    // Test 2s (file ids in driver info logs are replaced with file names)
+    let id_to_name = |id: u32| match id {
+        1 => Some("include1.glsl"),
+        2 => Some("include2.glsl"),
+        _ => None,
+    };
+    let nvidia = "0(12) : error C0000: syntax error, unexpected '}'\n\
+                  1(3) : error C1008: undefined variable \"common_uniform2\"";
+    assert_eq!(glossy::log::rewrite_file_ids(nvidia, id_to_name),
+               "0(12) : error C0000: syntax error, unexpected '}'\n\
+                include1.glsl(3) : error C1008: undefined variable \"common_uniform2\"");
+    let mesa = "2:1(17): error: syntax error, unexpected NEW_IDENTIFIER";
+    assert_eq!(glossy::log::rewrite_file_ids(mesa, id_to_name),
+               "include2.glsl:1(17): error: syntax error, unexpected NEW_IDENTIFIER");
+    let amd = "ERROR: 1:4: 'common_uniform2' : undeclared identifier\n\
+               ERROR: 1 compilation errors.  No code generated.";
+    assert_eq!(glossy::log::rewrite_file_ids(amd, id_to_name),
+               "ERROR: include1.glsl:4: 'common_uniform2' : undeclared identifier\n\
+                ERROR: 1 compilation errors.  No code generated.");
+}
+
+#[test]
+fn file_map() {
+    use glossy::files::FileMap;
+
+    // Test 2t (lookups in the file table, in both directions)
+    static FILES: FileMap = FileMap {
+        files: &[(1, "include1.glsl"), (2, "include2.glsl"), (3, "recurse.glsl")],
+        shaders: &[("common.glsl", &["include1.glsl", "include2.glsl"])],
+    };
+    assert_eq!(FILES.name(2), Some("include2.glsl"));
+    assert_eq!(FILES.name(4), None);
+    assert_eq!(FILES.id("include1.glsl"), Some(1));
+    assert_eq!(FILES.id("missing.glsl"), None);
+    assert_eq!(FILES.includes("common.glsl"),
+               Some(&["include1.glsl", "include2.glsl"][..]));
+    assert_eq!(FILES.translate_log("2:1(17): error: syntax error"),
+               "include2.glsl:1(17): error: syntax error");
+}
+
+#[cfg(feature = "spirv")]
+#[test]
+fn spirv() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2u (SPIR-V compiled from the processed source of a shader with an include)
+    Config::new(Language::OpenGl4x)
+        .vertex("tests/spirv.vert")
+        .include("tests/spirv.glsl")
+        .spirv()
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let binary = fs::read(Path::new(&out_dir).join("spirv.vert.spv")).unwrap();
+    assert_eq!(&binary[..4], &[0x03, 0x02, 0x23, 0x07]);
+    let words = fs::read_to_string(Path::new(&out_dir).join("spirv.vert.spv.rs")).unwrap();
+    assert!(words.starts_with("&[\n    0x07230203,"));
+    assert_eq!(words.matches(',').count() * 4, binary.len());
+}
+
+#[cfg(feature = "spirv")]
+#[test]
+#[should_panic(expected = "badspirv.glsl:2:")]
+fn spirv_error() {
+    setup();
+
+    // Test 2v (compilation errors are reported at the line of the included file)
+    Config::new(Language::OpenGl4x)
+        .vertex("tests/badspirv.vert")
+        .include("tests/badspirv.glsl")
+        .spirv()
+        .build();
+}
+
+#[cfg(feature = "wgsl")]
+#[test]
+fn wgsl() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2w (WGSL translated from the processed source of a shader with an include)
+    Config::new(Language::OpenGl4x)
+        .fragment("tests/wgsl.frag")
+        .include("tests/wgsl.glsl")
+        .wgsl()
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let wgsl = fs::read_to_string(Path::new(&out_dir).join("wgsl.frag.wgsl")).unwrap();
+    assert!(wgsl.contains("@fragment"));
+    assert!(wgsl.contains("fn tint("));
+}
+
+#[cfg(feature = "wgsl")]
+#[test]
+#[should_panic(expected = "badwgsl.glsl:3:")]
+fn wgsl_error() {
+    setup();
+
+    // Test 2x (translation errors are reported at the line of the included file)
+    Config::new(Language::OpenGl4x)
+        .fragment("tests/badwgsl.frag")
+        .include("tests/badwgsl.glsl")
+        .wgsl()
+        .build();
+}
+
+#[cfg(feature = "minifier")]
+#[test]
+fn minifier() {
+    use std::{env, fs};
+    use std::path::Path;
+    use glossy_codegen::OptimizerBackend;
+
+    setup();
+
+    // Test 2y (pure-Rust minification: constant folding, dead function removal and whitespace)
+    Config::new(Language::OpenGl)
+        .vertex("tests/minify.vert")
+        .include("tests/include*.glsl")
+        .optimizer_backend(OptimizerBackend::Minifier)
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("minify.vert")).unwrap();
+    assert_eq!(source,
+               "#version 120\n\
+                uniform float common_uniform;\n\
+                attribute vec2 position;\n\
+                varying vec2 uv;\n\
+                vec2 scale(vec2 v);\n\
+                void main(){uv=scale(position)*0.25;\
+                gl_Position=vec4(position- -1.0,2.0,1.0);}\n\
+                vec2 scale(vec2 v){return v*0.5+0.5;}\n");
+
+    // Each line maps to the original line of its first token
+    let map = fs::read_to_string(Path::new(&out_dir).join("minify.vert.map.rs")).unwrap();
+    let include = file_id(&map, "include1.glsl");
+    let vert = file_id(&map, "minify.vert");
+    assert!(map.contains(&format!("lines: &[None, Some(({}, 1)), Some(({}, 5)), Some(({}, 6)), \
+                                   Some(({}, 13)), Some(({}, 15)), Some(({}, 20)), ]",
+                                  include,
+                                  vert,
+                                  vert,
+                                  vert,
+                                  vert,
+                                  vert)));
+}
+
+#[test]
+fn transform() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 2z (user transforms before and after optimization)
+    Config::new(Language::OpenGl)
+        .vertex("tests/transform.vert")
+        .include("tests/include*.glsl")
+        .transform(Box::new(Replace {
+            from: "SCALE",
+            to: Some("0.5"),
+            after_optimization: false,
+        }))
+        .transform(Box::new(Replace {
+            from: "gl_Position",
+            to: Some("/* watermark */ gl_Position"),
+            after_optimization: true,
+        }))
+        .optimize()
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("transform.vert")).unwrap();
+    assert!(!source.contains("SCALE"));
+    assert!(source.contains("/* watermark */ gl_Position"));
+}
+
 #[test]
 #[should_panic]
 fn recurse() {
@@ -44,7 +724,8 @@ fn recurse() {
 }
 
 #[test]
-#[should_panic]
+#[should_panic(expected = "included file \"include2.glsl\" specifies version 120, but parent \
+                           specifies version 420")]
 fn version() {
     setup();
 
@@ -54,3 +735,341 @@ fn version() {
         .include("tests/include*.glsl")
         .build();
 }
+
+#[test]
+fn version_policy() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 5 (an older include is allowed by the NotNewer policy, any include by the Ignore and
+    // Warn policies, and the top-level version is used)
+    Config::new(Language::OpenGl)
+        .source("tests/version.glsl")
+        .include("tests/include*.glsl")
+        .version_policy(VersionPolicy::NotNewer)
+        .build();
+    Config::new(Language::OpenGl)
+        .fragment("tests/version_policy.frag")
+        .include("tests/version100.glsl")
+        .version_policy(VersionPolicy::Ignore)
+        .build();
+    Config::new(Language::OpenGl)
+        .source("tests/version.glsl")
+        .include("tests/include*.glsl")
+        .version_policy(VersionPolicy::Warn)
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("version.glsl")).unwrap();
+    assert!(source.starts_with("#version 420\n"));
+    assert!(!source.contains("#version 120"));
+}
+
+#[test]
+#[should_panic(expected = "included file \"version100.glsl\" specifies version 100, but parent \
+                           specifies version 120")]
+fn version_policy_violation() {
+    setup();
+
+    // Test 5a (GLSL ES 1.00 is not older than GLSL 1.20 under the NotNewer policy)
+    Config::new(Language::OpenGl)
+        .fragment("tests/version_policy.frag")
+        .include("tests/version100.glsl")
+        .version_policy(VersionPolicy::NotNewer)
+        .build();
+}
+
+#[cfg(feature = "minifier")]
+#[test]
+fn minifier_rename() {
+    use std::{env, fs};
+    use std::path::Path;
+    use glossy_codegen::OptimizerBackend;
+
+    setup();
+
+    // Test 6 (renaming of private identifiers, keeping interface names, and parentheses)
+    Config::new(Language::OpenGl)
+        .vertex("tests/rename.vert")
+        .optimizer_backend(OptimizerBackend::Minifier)
+        .optimizer_options(OptimizerOptions::new().rename_identifiers(true))
+        .build();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&out_dir).join("rename.vert")).unwrap();
+    assert_eq!(source,
+               "#version 120\n\
+                struct Light{vec3 position;float intensity;};\n\
+                uniform Light light;\n\
+                uniform mat4 transform;\n\
+                attribute vec3 position;\n\
+                varying float brightness;\n\
+                const float c=0.25;\n\
+                float d(float a,float intensity){float e=1.0/(1.0+a*a);return e*intensity;}\n\
+                void main(){float a=length(light.position-position);\
+                for(int b=0;b<2;b++){a=a*0.5;}brightness=c+d(a,light.intensity);\
+                gl_Position=transform*vec4(position,1.0);}\n");
+
+    // Identifiers may be macros when the source has other directives, so parentheses around
+    // them and within their arguments are kept, and constants are not folded
+    Config::new(Language::OpenGl)
+        .vertex("tests/define.vert")
+        .optimizer_backend(OptimizerBackend::Minifier)
+        .build();
+
+    let source = fs::read_to_string(Path::new(&out_dir).join("define.vert")).unwrap();
+    assert!(source.contains("gl_Position=vec4((OFFSET)*2.0,0.5,HALF(1.0+2.0),\
+                             SQ((position.x+position.y)));"));
+}
+
+#[test]
+#[should_panic(expected = "include1.glsl:4: found common_uniform")]
+fn transform_error() {
+    setup();
+
+    // Test 7 (transform errors are reported at the original file and line)
+    Config::new(Language::OpenGl)
+        .vertex("tests/source_map.vert")
+        .include("tests/include*.glsl")
+        .transform(Box::new(Replace {
+            from: "common_uniform * 2.0",
+            to: None,
+            after_optimization: false,
+        }))
+        .build();
+}
+
+#[cfg(feature = "minifier")]
+#[test]
+#[should_panic(expected = "include1.glsl:3: found common_uniform*2.0")]
+fn minifier_transform_error() {
+    use glossy_codegen::OptimizerBackend;
+
+    setup();
+
+    // Test 8 (errors in minified shaders are reported at the original file and line)
+    Config::new(Language::OpenGl)
+        .vertex("tests/source_map.vert")
+        .include("tests/include*.glsl")
+        .optimizer_backend(OptimizerBackend::Minifier)
+        .transform(Box::new(Replace {
+            from: "common_uniform*2.0",
+            to: None,
+            after_optimization: true,
+        }))
+        .build();
+}
+
+#[test]
+fn embed() {
+    use std::{env, fs};
+    use std::path::Path;
+    use glossy::archive::{Archive, ShaderInfo, Stage, Variable};
+    use glossy::reflect::BasicType;
+
+    setup();
+
+    // Test 9 (obfuscated, compressed and archived copies of the processed sources)
+    let config = Config::new(Language::OpenGl)
+        .vertex("tests/embed.vert")
+        .include("tests/include*.glsl")
+        .obfuscate(0x1234_5678_9abc_def0)
+        .archive("archive_test.glsa");
+    #[cfg(feature = "compress")]
+    let config = config.compress();
+    config.build();
+
+    let out_dir = Path::new(&env::var("OUT_DIR").unwrap()).to_path_buf();
+    let source = fs::read_to_string(out_dir.join("embed.vert")).unwrap();
+
+    // The obfuscated copies are decoded with the same key
+    let obfuscated = fs::read(out_dir.join("embed.vert.obf")).unwrap();
+    assert_eq!(obfuscated.len(), source.len() + 8);
+    assert!(!obfuscated.windows(4).any(|w| w == b"void"));
+    assert_eq!(glossy::obfuscate::decode(&obfuscated, 0x1234_5678_9abc_def0), source);
+    assert!(fs::read_to_string(out_dir.join("glossy_obfuscation_key.rs"))
+        .unwrap()
+        .starts_with("0x123456789abcdef0u64"));
+
+    // The compressed copies are decompressed to the same source
+    #[cfg(feature = "compress")]
+    {
+        let compressed = fs::read(out_dir.join("embed.vert.deflate")).unwrap();
+        assert_eq!(glossy::compress::decompress(&compressed), source);
+    }
+
+    // The archive holds the shaders and their metadata, and is read back at runtime
+    let mut file = fs::File::open(out_dir.join("archive_test.glsa")).unwrap();
+    let archive = Archive::read(&mut file).unwrap();
+    assert_eq!(archive.shaders.len(), 1);
+    let shader = archive.shader("embed.vert", Language::OpenGl).unwrap();
+    assert_eq!(shader.stage, Stage::Vertex);
+    assert_eq!(shader.version, "120");
+    assert_eq!(shader.source, source);
+    assert_eq!(shader.includes, vec!["include1.glsl".to_string()]);
+    assert!(shader.info.is_none());
+    let id = archive.files.iter().find(|&(_, name)| name == "include1.glsl").unwrap().0;
+    assert_eq!(archive.file_name(id), Some("include1.glsl"));
+
+    // Reflection info survives a round trip
+    let mut archive = archive;
+    archive.shaders[0].info = Some(ShaderInfo {
+        inputs: vec![Variable {
+                         name: "position".to_string(),
+                         ty: BasicType::Float,
+                         precision: Precision::High,
+                         vec_size: 2,
+                         mat_size: 1,
+                         array_size: None,
+                         location: Some(0),
+                     }],
+        uniforms: Vec::new(),
+        textures: Vec::new(),
+        uniform_total_size: 0,
+    });
+    let mut bytes = Vec::new();
+    archive.write(&mut bytes).unwrap();
+    assert_eq!(Archive::read(&mut &bytes[..]).unwrap(), archive);
+    assert!(Archive::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    assert!(Archive::read(&mut &b"GLOSSYAX"[..]).is_err());
+
+    // Unknown type codes are rejected rather than read as BasicType::Other
+    let name = bytes.windows(12).position(|w| w == b"\x08\0\0\0position").unwrap();
+    bytes[name + 12] = 8;
+    assert!(Archive::read(&mut &bytes[..]).is_err());
+    bytes[name + 12] = 255;
+    let archive = Archive::read(&mut &bytes[..]).unwrap();
+    assert_eq!(archive.shaders[0].info.as_ref().unwrap().inputs[0].ty, BasicType::Other);
+}
+
+#[test]
+fn out_dir() {
+    use std::{env, fs};
+    use std::path::Path;
+
+    setup();
+
+    // Test 10 (shaders written to an explicit directory instead of OUT_DIR)
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("explicit");
+    fs::create_dir_all(&out_path).unwrap();
+    Config::new(Language::OpenGl)
+        .vertex("tests/common.glsl")
+        .include("tests/include*.glsl")
+        .translate_from(Language::OpenGl)
+        .targets(&[Language::OpenGlEs20])
+        .out_dir(&out_path)
+        .build();
+
+    assert!(out_path.join("common.glsl").exists());
+    assert!(out_path.join("gles20/common.glsl").exists());
+    assert!(out_path.join("glossy_files.rs").exists());
+}
+
+#[test]
+fn try_build() {
+    setup();
+
+    // Test 11 (errors are returned by try_build instead of panicking)
+    let err = Config::new(Language::OpenGl)
+        .source("tests/recurse.glsl")
+        .include("tests/badinclude*.glsl")
+        .try_build()
+        .unwrap_err();
+    assert!(err.starts_with("recursive inclusion of"), "{}", err);
+}
+
+/// A transform which replaces a word in the source, or fails at the line of a word.
+struct Replace {
+    from: &'static str,
+    to: Option<&'static str>,
+    after_optimization: bool,
+}
+
+impl ShaderTransform for Replace {
+    fn transform(&self,
+                 source: &str,
+                 _kind: SourceKind,
+                 _lang: Language)
+                 -> Result<String, Diagnostics> {
+        match self.to {
+            Some(to) => Ok(source.replace(self.from, to)),
+            None => {
+                let line = source.lines().position(|line| line.contains(self.from)).unwrap();
+                Err(Diagnostics::new().error_at(line + 1, format!("found {}", self.from)))
+            }
+        }
+    }
+
+    fn after_optimization(&self) -> bool {
+        self.after_optimization
+    }
+}
+
+/// Checks that every non-empty line which follows a `#line` directive in a processed source is
+/// identical to the line of the original file it is attributed to. `map` is the generated source
+/// map, which gives the names of file ids, and `legacy` is true if the directive numbers itself
+/// rather than the following line, as in desktop GLSL before 3.30.
+fn check_lines(source: &str, map: &str, legacy: bool) {
+    use std::fs;
+
+    let mut location: Option<(String, usize)> = None;
+    for line in source.lines() {
+        if let Some(args) = line.strip_prefix("#line ") {
+            let mut args = args.splitn(2, ' ');
+            let number: usize = args.next().unwrap().parse().unwrap();
+            let file = args.next().unwrap();
+            let file = match file.parse::<u32>() {
+                Ok(id) => file_name(map, id),
+                Err(_) => file.trim_matches('"').to_string(),
+            };
+            location = Some((file, if legacy { number + 1 } else { number }));
+            continue;
+        }
+        if let Some((ref file, ref mut number)) = location {
+            if !line.is_empty() {
+                let original = fs::read_to_string(format!("tests/{}", file)).unwrap();
+                assert_eq!(original.lines().nth(*number - 1), Some(line));
+            }
+            *number += 1;
+        }
+    }
+    assert!(location.is_some());
+}
+
+/// Returns the name, type and vector size of every variable in one list of a generated
+/// `ShaderInfo` expression.
+fn info_variables(info: &str, list: &str) -> Vec<(String, String, u32)> {
+    let field = |var: &str, name: &str| {
+        let start = var.find(&format!("{}: ", name)).unwrap() + name.len() + 2;
+        let end = start + var[start..].find(',').unwrap();
+        var[start..end].to_string()
+    };
+    let start = info.find(&format!("    {}: &[\n", list)).unwrap();
+    let end = start + info[start..].find("    ],\n").unwrap();
+    info[start..end]
+        .lines()
+        .skip(1)
+        .map(|var| {
+            (field(var, "name").trim_matches('"').to_string(),
+             field(var, "ty").rsplit("::").next().unwrap().to_string(),
+             field(var, "vec_size").parse().unwrap())
+        })
+        .collect()
+}
+
+/// Returns the id of a file in a generated source map.
+fn file_id(map: &str, name: &str) -> u32 {
+    let end = map.find(&format!(", {:?})", name)).unwrap();
+    let start = map[..end].rfind('(').unwrap() + 1;
+    map[start..end].parse().unwrap()
+}
+
+/// Returns the name of a file in a generated source map.
+fn file_name(map: &str, id: u32) -> String {
+    let prefix = format!("({}, \"", id);
+    let start = map.find(&prefix).unwrap() + prefix.len();
+    let end = start + map[start..].find('"').unwrap();
+    map[start..end].to_string()
+}
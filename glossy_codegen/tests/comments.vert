@@ -0,0 +1,11 @@
+/* a */ #version 120 /* b */ // c
+/* d */ #include "include1.glsl" /* e */ /* f */
+/* // g */ #include "include2.glsl"
+// /* h
+#define SCALE \
+    2.0
+/* i
+#include "block_dummy.glsl"
+// j */ void main() {
+    gl_Position = vec4(common_func() * SCALE);
+}
@@ -0,0 +1,16 @@
+#version 100
+#ifdef GL_EXT_shader_texture_lod
+#extension GL_EXT_shader_texture_lod : enable
+#endif
+#ifdef GL_OES_standard_derivatives
+#include "extension1.glsl"
+#endif
+precision mediump float;
+
+void main() {
+#ifdef GL_OES_standard_derivatives
+    gl_FragColor = vec4(edge(0.5));
+#else
+    gl_FragColor = vec4(0.5);
+#endif
+}
@@ -0,0 +1,10 @@
+#version 120
+#define OFFSET position + 1.0
+#define HALF(x) x * 0.5
+#define SQ(x) x * x
+
+attribute vec2 position;
+
+void main() {
+    gl_Position = vec4((OFFSET) * 2.0, (0.5), HALF(1.0 + 2.0), SQ((position.x + position.y)));
+}
@@ -0,0 +1,7 @@
+#version 120
+#include "include1.glsl"
+
+// common_func is declared in include1.glsl
+void main() {
+    gl_Position = vec4(common_func());
+}
@@ -0,0 +1,8 @@
+#version 100
+#include "extension1.glsl"
+precision mediump float;
+#include "extension2.glsl"
+
+void main() {
+    gl_FragColor = vec4(edge(0.5) + edge2(0.5));
+}
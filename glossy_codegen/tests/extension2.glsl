@@ -0,0 +1,5 @@
+#extension GL_OES_standard_derivatives : require
+#extension GL_EXT_shader_texture_lod : enable
+float edge2(float x) {
+    return fwidth(x) * 2.0;
+}
@@ -0,0 +1 @@
+#extension GL_OES_standard_derivatives : disable
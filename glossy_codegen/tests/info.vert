@@ -0,0 +1,8 @@
+#version 120
+#include "include1.glsl"
+
+attribute vec2 position;
+
+void main() {
+    gl_Position = vec4(position * common_func(), 0.0, 1.0);
+}
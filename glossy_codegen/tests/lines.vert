@@ -0,0 +1,11 @@
+// The #version directive need not be on the first line
+
+#version 120
+#include "include1.glsl"
+#extension GL_EXT_gpu_shader4 : enable
+/* a block comment
+   before a directive */ #include "include2.glsl"
+
+void main() {
+    gl_Position = vec4(common_func());
+}
@@ -0,0 +1,22 @@
+#version 120
+
+#include "include1.glsl"
+
+attribute vec2 position;
+varying vec2 uv;
+
+// Never called
+float unused(float x) {
+    return x * 2.0;
+}
+
+vec2 scale(vec2 v);
+
+void main() {
+    uv = scale(position) * (1.0 / 4.0);
+    gl_Position = vec4(position - -1.0, 0.0 + 1.0 * 2.0, 1.0);
+}
+
+vec2 scale(vec2 v) {
+    return v * 0.5 + 0.5;
+}
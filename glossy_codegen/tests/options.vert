@@ -0,0 +1,11 @@
+#version 120
+#include "include1.glsl"
+
+// Never called, so it is only kept in sources which are not full shaders
+float unused_helper(float x) {
+    return x * 3.0;
+}
+
+void main() {
+    gl_Position = vec4(common_func(), 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,8 @@
+#version 100
+#include "include1.glsl"
+
+uniform lowp vec4 tint;
+
+void main() {
+    gl_FragColor = tint * common_func();
+}
@@ -0,0 +1,10 @@
+#version 300 es
+#ifdef GL_EXT_shader_framebuffer_fetch
+#extension GL_EXT_shader_framebuffer_fetch : require
+#endif
+
+out vec4 color;
+
+void main() {
+    color = vec4(0.5);
+}
@@ -0,0 +1,8 @@
+#version 300 es
+#include "version100.glsl"
+precision mediump float;
+out vec4 color;
+
+void main() {
+    color = vec4(lib_func(1.0));
+}
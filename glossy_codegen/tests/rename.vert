@@ -0,0 +1,27 @@
+#version 120
+
+struct Light {
+    vec3 position;
+    float intensity;
+};
+
+uniform Light light;
+uniform mat4 transform;
+attribute vec3 position;
+varying float brightness;
+
+const float ambient = (0.25);
+
+float attenuate(float distance, float intensity) {
+    float falloff = 1.0 / (1.0 + distance * distance);
+    return (falloff * intensity);
+}
+
+void main() {
+    float distance = length(light.position - position);
+    for (int count = 0; count < 2; count++) {
+        distance = (distance) * 0.5;
+    }
+    brightness = ambient + attenuate(distance, light.intensity);
+    gl_Position = transform * vec4(position, 1.0);
+}
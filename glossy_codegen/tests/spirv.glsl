@@ -0,0 +1,3 @@
+vec2 scale(vec2 v) {
+    return v * 0.5 + 0.5;
+}
@@ -0,0 +1,11 @@
+#version 450
+
+#include "spirv.glsl"
+
+layout(location = 0) in vec2 position;
+layout(location = 0) out vec2 uv;
+
+void main() {
+    uv = scale(position);
+    gl_Position = vec4(position, 0.0, 1.0);
+}
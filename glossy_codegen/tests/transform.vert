@@ -0,0 +1,6 @@
+#version 120
+#include "include1.glsl"
+
+void main() {
+    gl_Position = vec4(common_func() * SCALE);
+}
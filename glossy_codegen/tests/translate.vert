@@ -0,0 +1,8 @@
+#version 120
+attribute vec2 position;
+varying vec2 uv;
+
+void main() {
+    uv = position;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
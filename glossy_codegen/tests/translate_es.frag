@@ -0,0 +1,10 @@
+#version 300 es
+precision mediump float;
+uniform sampler2D tex;
+uniform samplerCube env;
+in vec2 uv;
+layout(location = 0) out vec4 color;
+
+void main() {
+    color = texture(tex, uv) + texture(env, vec3(uv, 1.0));
+}
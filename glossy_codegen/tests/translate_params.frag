@@ -0,0 +1,18 @@
+#version 300 es
+precision mediump float;
+in vec2 uv;
+
+
+out vec4 color;
+
+vec4 shade(
+    in vec4 base,
+    out float alpha) {
+    alpha = base.a;
+    return base;
+}
+
+void main() {
+    float alpha;
+    color = shade(vec4(uv, 0.0, 1.0), alpha);
+}
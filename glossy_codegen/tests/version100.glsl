@@ -0,0 +1,4 @@
+#version 100
+float lib_func(float x) {
+    return x * 0.5;
+}
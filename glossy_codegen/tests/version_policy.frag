@@ -0,0 +1,6 @@
+#version 120
+#include "version100.glsl"
+
+void main() {
+    gl_FragColor = vec4(lib_func(1.0));
+}
@@ -0,0 +1,13 @@
+#version 100
+#include "webgl_common.glsl"
+
+uniform vec4 colors[COLOR_COUNT];
+varying float fade;
+
+void main() {
+    vec4 sum = vec4(0.0);
+    for (int i = 0; i < COLOR_COUNT; i++) {
+        sum += colors[i];
+    }
+    gl_FragColor = sum * fade;
+}
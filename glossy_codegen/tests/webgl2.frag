@@ -0,0 +1,7 @@
+#version 300 es
+in float fade;
+out vec4 color;
+
+void main() {
+    color = vec4(fade);
+}
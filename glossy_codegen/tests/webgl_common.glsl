@@ -0,0 +1,2 @@
+#extension GL_OES_standard_derivatives : enable
+#define COLOR_COUNT 4
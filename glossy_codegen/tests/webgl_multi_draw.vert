@@ -0,0 +1,10 @@
+#version 100
+#extension GL_ANGLE_multi_draw : require
+
+attribute vec2 position;
+varying float fade;
+
+void main() {
+    fade = float(gl_DrawID);
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 450
+
+#include "wgsl.glsl"
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+void main() {
+    color = tint(vec4(uv, 0.0, 1.0));
+}
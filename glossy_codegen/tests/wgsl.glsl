@@ -0,0 +1,3 @@
+vec4 tint(vec4 c) {
+    return c * vec4(1.0, 0.5, 0.5, 1.0);
+}
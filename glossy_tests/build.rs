@@ -0,0 +1,17 @@
+extern crate glossy_codegen as glsl;
+
+fn main() {
+    println!("cargo:rerun-if-changed=../glossy_codegen/tests");
+    glsl::Config::new(glsl::Language::OpenGl)
+        .source("../glossy_codegen/tests/targets.frag")
+        .translate_from(glsl::Language::OpenGl)
+        .targets(&[glsl::Language::OpenGlEs20, glsl::Language::OpenGlEs30])
+        .build();
+    glsl::Config::new(glsl::Language::OpenGl)
+        .vertex("../glossy_codegen/tests/source_map.vert")
+        .include("../glossy_codegen/tests/include*.glsl")
+        .optimizer_backend(glsl::OptimizerBackend::Minifier)
+        .obfuscate(0x1234_5678_9abc_def0)
+        .compress()
+        .build();
+}
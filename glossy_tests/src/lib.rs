@@ -0,0 +1,3 @@
+//! This crate only exists to test the macros of glossy, which need a build script that runs
+//! glossy_codegen. The shaders are built by `build.rs` from the glossy_codegen test fixtures, and
+//! the tests are in `tests/macros.rs`.
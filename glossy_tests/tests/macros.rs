@@ -0,0 +1,67 @@
+#[macro_use]
+extern crate glossy;
+
+use glossy::Language;
+use glossy::files::FileMap;
+
+static FILES: FileMap = shader_files!();
+
+#[test]
+fn targets() {
+    // The main language is selected from the top-level output, and other targets from their
+    // subdirectories
+    assert_eq!(shader!("targets.frag", Language::OpenGl), shader!("targets.frag"));
+    assert!(shader!("targets.frag").starts_with("#version 120\n"));
+    assert!(shader!("targets.frag", Language::OpenGlEs20).starts_with("#version 100\n"));
+    assert!(shader!("targets.frag", Language::OpenGlEs30).starts_with("#version 300 es\n"));
+}
+
+#[test]
+#[should_panic(expected = "shader was not built for the requested language")]
+fn missing_target() {
+    shader!("targets.frag", Language::WebGl2);
+}
+
+#[test]
+fn minified_source_map() {
+    // Errors in minified shaders are reported at the file and line of the first token of the line
+    let map = shader_source_map!("source_map.vert");
+    let line = shader!("source_map.vert")
+        .lines()
+        .position(|line| line.contains("common_uniform*2.0"))
+        .unwrap() + 1;
+    assert_eq!(map.translate_log(&format!("0({}) : error C1008: undefined variable", line)),
+               "include1.glsl(3) : error C1008: undefined variable");
+}
+
+#[test]
+fn files() {
+    // The generated table lists the files of the last build, in both directions
+    let id = FILES.id("include1.glsl").unwrap();
+    assert_eq!(FILES.name(id), Some("include1.glsl"));
+    assert_eq!(shader_id_to_name!(id), Some("include1.glsl"));
+    assert_eq!(FILES.includes("source_map.vert"), Some(&["include1.glsl"][..]));
+    assert_eq!(FILES.name(0), None);
+
+    let log = format!("{}:4(12): error: undefined variable", id);
+    assert_eq!(FILES.translate_log(&log), "include1.glsl:4(12): error: undefined variable");
+    assert_eq!(translate_shader_log!(&log), FILES.translate_log(&log));
+}
+
+#[test]
+fn obfuscated() {
+    // The obfuscated copy decodes to the processed source, which it does not contain as text
+    assert_eq!(shader_obfuscated!("source_map.vert"), shader!("source_map.vert"));
+    let data = include_bytes!(concat!(env!("OUT_DIR"), "/source_map.vert.obf"));
+    assert!(!data.windows(4).any(|window| window == b"void"));
+}
+
+#[test]
+fn compressed() {
+    // The compressed copy is decompressed on first access, and cached afterwards
+    fn source() -> &'static str {
+        shader_compressed!("source_map.vert")
+    }
+    assert_eq!(source(), shader!("source_map.vert"));
+    assert_eq!(source().as_ptr(), source().as_ptr());
+}